@@ -0,0 +1,610 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{DefaultBodyLimit, Path, State},
+    http::{HeaderMap, HeaderValue, Request, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    config::{ApiConfig, InputConfig, RateLimitConfig},
+    executors::{ExecutionRequest, ExecutionResult, Executor, SourceFile},
+    rate_limit::RateLimiter,
+    runners::LANGUAGES,
+    scheduler::Scheduler,
+    utils::constant_time_eq,
+};
+
+const OPENAPI: &str = include_str!("openapi.json");
+
+struct Api {
+    executor: Arc<dyn Executor>,
+    scheduler: Arc<Scheduler>,
+    rate_limiter: RateLimiter,
+    input: InputConfig,
+    keys: Vec<String>,
+    job_retention: Duration,
+    jobs: Mutex<HashMap<Uuid, Job>>,
+}
+
+/// Index into the configured keys of the key a request was made with.
+#[derive(Clone, Copy)]
+struct ApiKey(usize);
+
+struct Job {
+    key: usize,
+    state: JobState,
+    finished_at: Option<Instant>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum JobState {
+    Queued { position: usize },
+    Running,
+    Finished { result: ExecutionResult },
+    Failed { error: String },
+}
+
+#[derive(Deserialize)]
+struct ExecuteRequest {
+    language: String,
+    /// A single source file, in place of `files`.
+    code: Option<String>,
+    #[serde(default)]
+    files: Vec<SourceFile>,
+    #[serde(default)]
+    stdin: String,
+}
+
+/// Serves the HTTP API, running jobs with the bot's executor and queueing them in its scheduler
+/// so they share its limits with jobs from Discord. The document at `GET /openapi.json`
+/// describes the endpoints, every other one needs one of the configured keys as a bearer token.
+///
+/// - `POST /execute` queues a job and answers with its ID.
+/// - `GET /jobs/{id}` reports where the job is, and its result once it has finished.
+/// - `GET /languages` lists the languages jobs can be written in.
+pub async fn serve(
+    config: ApiConfig,
+    input: InputConfig,
+    rate_limit: RateLimitConfig,
+    executor: Arc<dyn Executor>,
+    scheduler: Arc<Scheduler>,
+) -> Result<(), String> {
+    if config.keys.iter().any(|key| key.is_empty()) {
+        return Err("API keys can't be empty".into());
+    }
+    if config.keys.is_empty() {
+        tracing::warn!("No API keys are configured, every request will be refused");
+    }
+
+    let address = config
+        .listen
+        .parse::<SocketAddr>()
+        .map_err(|e| format!("Invalid API listen address {}: {e}", config.listen))?;
+
+    let app = app(config, input, rate_limit, executor, scheduler);
+
+    tracing::info!("API listening on {address}");
+    axum::Server::try_bind(&address)
+        .map_err(|e| format!("Failed to listen on {address}: {e}"))?
+        .serve(app.into_make_service())
+        .await
+        .map_err(|e| format!("API server failed: {e}"))
+}
+
+/// Routes of the API, running jobs with `executor`.
+fn app(
+    config: ApiConfig,
+    input: InputConfig,
+    rate_limit: RateLimitConfig,
+    executor: Arc<dyn Executor>,
+    scheduler: Arc<Scheduler>,
+) -> Router {
+    // JSON escaping can make a request several times larger than the code and input in it
+    let body_limit = 8 * (input.max_source_size + input.max_stdin_size) + 64 * 1024;
+    let api = Arc::new(Api {
+        executor,
+        scheduler,
+        rate_limiter: RateLimiter::new(rate_limit),
+        input,
+        keys: config.keys,
+        job_retention: Duration::from_secs(config.job_retention),
+        jobs: Mutex::new(HashMap::new()),
+    });
+
+    Router::new()
+        .route("/execute", post(execute))
+        .route("/jobs/:id", get(job))
+        .route("/languages", get(languages))
+        .route_layer(middleware::from_fn_with_state(api.clone(), authenticate))
+        .route("/openapi.json", get(openapi))
+        .layer(DefaultBodyLimit::max(body_limit))
+        .with_state(api)
+}
+
+/// Rejects requests without a configured key, and tells handlers which key was used.
+async fn authenticate<B>(
+    State(api): State<Arc<Api>>,
+    headers: HeaderMap,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    // Compare against every key so the time taken doesn't give away which one matched
+    let key = token.and_then(|token| {
+        api.keys
+            .iter()
+            .enumerate()
+            .fold(None, |found, (index, key)| {
+                constant_time_eq(token.as_bytes(), key.as_bytes())
+                    .then_some(index)
+                    .or(found)
+            })
+    });
+
+    match key {
+        Some(key) => {
+            request.extensions_mut().insert(ApiKey(key));
+            next.run(request).await
+        }
+        None => error(StatusCode::UNAUTHORIZED, "Missing or unknown API key"),
+    }
+}
+
+async fn execute(
+    State(api): State<Arc<Api>>,
+    Extension(ApiKey(key)): Extension<ApiKey>,
+    Json(body): Json<ExecuteRequest>,
+) -> Response {
+    let language = body.language.to_lowercase();
+    if !api
+        .executor
+        .supported_languages()
+        .contains(&language.as_str())
+    {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("Unsupported language: {}", body.language),
+        );
+    }
+
+    let files = match (body.code, body.files) {
+        (Some(contents), files) if files.is_empty() => vec![SourceFile {
+            name: String::new(),
+            contents,
+        }],
+        (None, files) => files,
+        (Some(_), _) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "Send either code or files, not both",
+            );
+        }
+    };
+    for file in files.iter().filter(|file| !file.name.is_empty()) {
+        if let Err(e) = SourceFile::validate_name(&file.name) {
+            return error(StatusCode::BAD_REQUEST, &e);
+        }
+    }
+
+    let request = ExecutionRequest {
+        language,
+        files,
+        stdin: body.stdin,
+    };
+    if request.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "No code to run");
+    }
+    if let Err(e) = request.validate(&api.input) {
+        return error(StatusCode::PAYLOAD_TOO_LARGE, &e);
+    }
+
+    if let Err(limited) = api.rate_limiter.check_api_key(key as u64) {
+        let mut response = error(StatusCode::TOO_MANY_REQUESTS, &limited.to_string());
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(limited.retry_after.as_secs_f64().ceil() as u64),
+        );
+        return response;
+    }
+
+    // Keys are queued as users of their own, numbered down from the top so they can't collide
+    // with Discord user IDs
+    let mut ticket = api
+        .scheduler
+        .enqueue(u64::MAX - key as u64, &request.language);
+    let id = Uuid::new_v4();
+    let position = ticket.position();
+    let state = match position {
+        0 => JobState::Running,
+        position => JobState::Queued { position },
+    };
+    let mut body = serde_json::to_value(&state).unwrap_or_default();
+    body["id"] = json!(id);
+
+    {
+        let mut jobs = api.jobs.lock().unwrap();
+        jobs.retain(|_, job| {
+            job.finished_at
+                .is_none_or(|finished_at| finished_at.elapsed() < api.job_retention)
+        });
+        jobs.insert(
+            id,
+            Job {
+                key,
+                state,
+                finished_at: None,
+            },
+        );
+    }
+
+    let runner = api.clone();
+    tokio::spawn(async move {
+        let mut position = position;
+        while position > 0 {
+            position = ticket.position_changed().await;
+            if position > 0 {
+                runner.update(id, JobState::Queued { position });
+            }
+        }
+
+        runner.update(id, JobState::Running);
+        tracing::info!("Running API job for language: {}", request.language);
+        let state = match runner.executor.execute(&request).await {
+            Ok(result) => JobState::Finished { result },
+            Err(error) => JobState::Failed { error },
+        };
+        drop(ticket);
+
+        runner.update(id, state);
+    });
+
+    let mut response = (StatusCode::ACCEPTED, Json(body)).into_response();
+    if let Ok(location) = HeaderValue::from_str(&format!("/jobs/{id}")) {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    response
+}
+
+async fn job(
+    State(api): State<Arc<Api>>,
+    Extension(ApiKey(key)): Extension<ApiKey>,
+    Path(id): Path<Uuid>,
+) -> Response {
+    let jobs = api.jobs.lock().unwrap();
+
+    // Jobs of other keys are reported as missing rather than forbidden, so IDs can't be probed
+    match jobs.get(&id).filter(|job| job.key == key) {
+        Some(job) => {
+            let mut body = serde_json::to_value(&job.state).unwrap_or_default();
+            body["id"] = json!(id);
+            Json(body).into_response()
+        }
+        None => error(StatusCode::NOT_FOUND, "No such job"),
+    }
+}
+
+async fn languages(State(api): State<Arc<Api>>) -> Json<serde_json::Value> {
+    let supported = api.executor.supported_languages();
+    let mut languages = LANGUAGES
+        .iter()
+        .filter(|(name, _)| supported.contains(name))
+        .map(
+            |(name, language)| json!({ "name": name, "file_extension": language.file_extension() }),
+        )
+        .collect::<Vec<_>>();
+    languages.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    Json(json!({ "languages": languages }))
+}
+
+async fn openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI)
+}
+
+impl Api {
+    fn update(&self, id: Uuid, state: JobState) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&id) {
+            if matches!(state, JobState::Finished { .. } | JobState::Failed { .. }) {
+                job.finished_at = Some(Instant::now());
+            }
+            job.state = state;
+        }
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use async_trait::async_trait;
+    use reqwest::Client;
+    use serde_json::Value;
+    use tokio::sync::Semaphore;
+
+    use super::*;
+    use crate::{
+        config::{BucketConfig, SchedulerConfig},
+        executors::MockExecutor,
+    };
+
+    const KEYS: [&str; 2] = ["first-key", "second-key"];
+
+    /// Holds every job until it is let through, then runs it with the mock backend.
+    struct Gate {
+        open: Semaphore,
+        mock: MockExecutor,
+    }
+
+    #[async_trait]
+    impl Executor for Gate {
+        async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+            self.open.acquire().await.unwrap().forget();
+            self.mock.execute(request).await
+        }
+
+        fn supported_languages(&self) -> Vec<&'static str> {
+            self.mock.supported_languages()
+        }
+    }
+
+    struct TestApi {
+        url: String,
+        client: Client,
+        gate: Arc<Gate>,
+    }
+
+    impl TestApi {
+        /// Serves the API on a free local port, running one job at a time and allowing each key
+        /// `capacity` jobs.
+        fn spawn(capacity: u32) -> Self {
+            let config = ApiConfig {
+                keys: KEYS.map(String::from).to_vec(),
+                ..ApiConfig::default()
+            };
+            let rate_limit = RateLimitConfig {
+                api_key: BucketConfig {
+                    capacity,
+                    refill_interval: 60,
+                },
+                ..RateLimitConfig::default()
+            };
+            let scheduler = Arc::new(Scheduler::new(SchedulerConfig {
+                max_jobs: 1,
+                ..SchedulerConfig::default()
+            }));
+            let gate = Arc::new(Gate {
+                open: Semaphore::new(0),
+                mock: MockExecutor::new(),
+            });
+            let app = app(
+                config,
+                InputConfig::default(),
+                rate_limit,
+                gate.clone(),
+                scheduler,
+            );
+
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(app.into_make_service()),
+            );
+
+            Self {
+                url: format!("http://{address}"),
+                client: Client::new(),
+                gate,
+            }
+        }
+
+        async fn execute(&self, key: &str, body: Value) -> reqwest::Response {
+            self.client
+                .post(format!("{}/execute", self.url))
+                .bearer_auth(key)
+                .json(&body)
+                .send()
+                .await
+                .unwrap()
+        }
+
+        async fn job(&self, key: &str, id: &str) -> (StatusCode, Value) {
+            let response = self
+                .client
+                .get(format!("{}/jobs/{id}", self.url))
+                .bearer_auth(key)
+                .send()
+                .await
+                .unwrap();
+            (response.status(), response.json().await.unwrap())
+        }
+
+        /// Polls the job until it has finished.
+        async fn finished(&self, key: &str, id: &str) -> Value {
+            for _ in 0..100 {
+                let (_, job) = self.job(key, id).await;
+                if job["state"] == "finished" {
+                    return job;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("job {id} didn't finish");
+        }
+    }
+
+    fn python(code: &str) -> Value {
+        json!({ "language": "python", "code": code, "stdin": "input\n" })
+    }
+
+    #[tokio::test]
+    async fn refuses_missing_and_unknown_keys() {
+        let api = TestApi::spawn(0);
+
+        let response = api
+            .client
+            .get(format!("{}/languages", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = api.execute("wrong-key", python("print(1)")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = response.json::<Value>().await.unwrap();
+        assert_eq!(body["error"], "Missing or unknown API key");
+
+        // The API's description is public
+        let response = api
+            .client
+            .get(format!("{}/openapi.json", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn runs_jobs_from_queued_to_finished() {
+        let api = TestApi::spawn(0);
+
+        let response = api.execute(KEYS[0], python("print(1)")).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let first = response.json::<Value>().await.unwrap();
+        assert_eq!(first["state"], "running");
+
+        // Only one job runs at a time, so the next one waits behind it
+        let response = api.execute(KEYS[1], python("print(2)")).await;
+        let location = response.headers()[header::LOCATION].clone();
+        let second = response.json::<Value>().await.unwrap();
+        let id = second["id"].as_str().unwrap();
+        assert_eq!(location, format!("/jobs/{id}").as_str());
+        assert_eq!(second["state"], "queued");
+        assert_eq!(second["position"], 1);
+        assert_eq!(api.job(KEYS[1], id).await.1["state"], "queued");
+
+        api.gate.open.add_permits(2);
+        let job = api.finished(KEYS[1], id).await;
+        assert_eq!(job["id"], id);
+        assert_eq!(job["result"]["status"], "completed");
+        assert_eq!(job["result"]["exit_code"], 0);
+        assert_eq!(job["result"]["stdout"], "input\nmain.py (8 bytes)\n");
+    }
+
+    #[tokio::test]
+    async fn keys_only_see_their_own_jobs() {
+        let api = TestApi::spawn(0);
+
+        let response = api.execute(KEYS[0], python("print(1)")).await;
+        let job = response.json::<Value>().await.unwrap();
+        let id = job["id"].as_str().unwrap();
+
+        assert_eq!(api.job(KEYS[0], id).await.0, StatusCode::OK);
+        let (status, body) = api.job(KEYS[1], id).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "No such job");
+        let (status, _) = api.job(KEYS[0], &Uuid::new_v4().to_string()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_each_key() {
+        let api = TestApi::spawn(1);
+
+        let response = api.execute(KEYS[0], python("print(1)")).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = api.execute(KEYS[0], python("print(1)")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = response.headers()[header::RETRY_AFTER].to_str().unwrap();
+        assert!((1..=60).contains(&retry_after.parse::<u64>().unwrap()));
+
+        // Other keys have buckets of their own
+        let response = api.execute(KEYS[1], python("print(1)")).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn refuses_invalid_jobs() {
+        let api = TestApi::spawn(0);
+        let refused = async |body| {
+            let response = api.execute(KEYS[0], body).await;
+            let status = response.status();
+            let body = response.json::<Value>().await.unwrap();
+            (status, body["error"].as_str().unwrap().to_string())
+        };
+
+        let both = json!({
+            "language": "python",
+            "code": "print(1)",
+            "files": [{ "name": "main.py", "contents": "print(2)" }],
+        });
+        assert_eq!(
+            refused(both).await,
+            (
+                StatusCode::BAD_REQUEST,
+                "Send either code or files, not both".into()
+            )
+        );
+
+        let unsupported = json!({ "language": "cobol", "code": "DISPLAY 'HI'." });
+        assert_eq!(
+            refused(unsupported).await,
+            (
+                StatusCode::BAD_REQUEST,
+                "Unsupported language: cobol".into()
+            )
+        );
+
+        let empty = json!({ "language": "python", "code": "  " });
+        assert_eq!(
+            refused(empty).await,
+            (StatusCode::BAD_REQUEST, "No code to run".into())
+        );
+
+        let escaping = json!({
+            "language": "python",
+            "files": [{ "name": "../main.py", "contents": "print(1)" }],
+        });
+        assert_eq!(refused(escaping).await.0, StatusCode::BAD_REQUEST);
+    }
+}
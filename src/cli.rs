@@ -0,0 +1,146 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fs, path::Path};
+
+use crate::{
+    config::BotConfig,
+    executors::{self, ExecutionRequest, ExecutionStatus, SourceFile},
+    render::Report,
+    runners::language_for_extension,
+};
+
+const USAGE: &str = "Usage:
+  compiler-bot run [--lang <language>] [--stdin <file>] <file>...
+  compiler-bot languages";
+
+/// Runs a command given on the command line instead of connecting to Discord, for trying out
+/// runners without a bot token. Returns the status to exit with.
+///
+/// - `run` runs source files with the configured backend and prints the result as the bot
+///   would show it. The language is inferred from the first file's extension unless given.
+/// - `languages` lists the languages the backend runs.
+pub async fn main(config: BotConfig, args: &[String]) -> Result<i32, String> {
+    match args {
+        [command, args @ ..] if command == "run" => run(&config, args).await,
+        [command] if command == "languages" => {
+            let mut languages = executors::for_single_run(&config).supported_languages();
+            languages.sort();
+            println!("Supported Programming Languages");
+            for language in languages {
+                println!("  {language}");
+            }
+            Ok(0)
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+async fn run(config: &BotConfig, args: &[String]) -> Result<i32, String> {
+    let mut language = None;
+    let mut stdin_path = None;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lang" | "-l" => language = Some(args.next().ok_or(USAGE)?.to_lowercase()),
+            "--stdin" | "-i" => stdin_path = Some(args.next().ok_or(USAGE)?),
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {flag}\n{USAGE}")),
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let language = match language {
+        Some(language) => language,
+        None => Path::new(paths[0])
+            .extension()
+            .and_then(|extension| language_for_extension(extension.to_str()?))
+            .map(|language| language.name().to_string())
+            .ok_or("Can't tell the language from the file extension, pass --lang")?,
+    };
+
+    let files = paths
+        .iter()
+        .map(|path| {
+            let name = Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| format!("Invalid file path {path}"))?;
+            let contents =
+                fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+            Ok(SourceFile {
+                name: name.into(),
+                contents,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let stdin = match stdin_path {
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?
+        }
+        None => String::new(),
+    };
+
+    let request = ExecutionRequest {
+        language,
+        files,
+        stdin,
+    };
+    request.validate(&config.input)?;
+
+    let executor = executors::for_single_run(config);
+    if !executor
+        .supported_languages()
+        .contains(&request.language.as_str())
+    {
+        return Err(format!("Unsupported language: {}", request.language));
+    }
+
+    print(&Report::pending(
+        &request.files,
+        &request.language,
+        0,
+        &config.output,
+    ));
+    match executor.execute(&request).await {
+        Ok(result) => {
+            print(&Report::result(&result, &config.output));
+            Ok(match result.status {
+                ExecutionStatus::Completed => result.exit_code.unwrap_or(1),
+                _ => 1,
+            })
+        }
+        Err(error) => {
+            print(&Report::failure(
+                &error,
+                &request.files,
+                &request.language,
+                &config.output,
+            ));
+            Ok(1)
+        }
+    }
+}
+
+fn print(report: &Report) {
+    println!("{}\n", report.to_plain_text());
+}
@@ -16,131 +16,177 @@
  * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use poise::CreateReply;
+use async_trait::async_trait;
+use poise::{CreateReply, ReplyHandle};
 
 use crate::{
-    CompilerBotContext, CompilerBotError, docker_executor::DockerExecutor, prelude::*,
-    utils::extract_code_block,
+    CompilerBotContext, CompilerBotError,
+    compiler::{self, Origin, Reply},
+    executors::OutputFile,
+    prelude::*,
+    render::Report,
+    utils::is_image,
 };
 
 /// Compile and run code
-#[poise::command(prefix_command)]
+///
+/// Code can be given as a code block or as an attached source file, in which case the language
+/// is inferred from its extension. Projects of several files can be sent as code blocks whose
+/// first line is a `// file: <name>` label, or as an attached zip archive. An attached `.txt`
+/// file is passed to the program as stdin.
+#[poise::command(prefix_command, check = "check_rate_limit", check = "check_quota")]
 pub async fn compile(
     ctx: CompilerBotContext<'_>,
-    #[description = "The language to compile the code in"] language: String,
+    #[description = "The language to compile the code in"] language: Option<String>,
     #[description = "The code to compile"]
     #[rest]
-    code: String,
+    code: Option<String>,
 ) -> Result<(), CompilerBotError> {
-    let code_block = extract_code_block(&code);
-    if code_block.is_none() {
-        ctx.say("No code block found").await?;
-        return Ok(());
+    let attachments = match ctx {
+        poise::Context::Prefix(prefix) => prefix.msg.attachments.clone(),
+        poise::Context::Application(_) => Vec::new(),
+    };
+    let attachments = attachments
+        .iter()
+        .map(|attachment| attachment as &dyn compiler::Attachment)
+        .collect::<Vec<_>>();
+
+    let compiler = &ctx.data().compiler;
+    let job = match compiler
+        .parse(language, code.as_deref(), &attachments)
+        .await
+    {
+        Ok(job) => job,
+        Err(error) => {
+            ctx.say(error).await?;
+            return Ok(());
+        }
+    };
+
+    let mut reply = EmbedReply { ctx, handle: None };
+    compiler.run(&origin(ctx), job, &mut reply).await?;
+
+    Ok(())
+}
+
+/// Refuses the command once the user, channel or guild goes over its rate limit, unless the user
+/// has an exempt role.
+async fn check_rate_limit(ctx: CompilerBotContext<'_>) -> Result<bool, CompilerBotError> {
+    let compiler = &ctx.data().compiler;
+
+    if compiler.rate_limiter.has_exempt_roles()
+        && let Some(member) = ctx.author_member().await
+    {
+        let roles = member
+            .roles
+            .iter()
+            .map(|role| role.get())
+            .collect::<Vec<_>>();
+        if compiler.rate_limiter.is_exempt(&roles) {
+            return Ok(true);
+        }
     }
 
-    let code_to_execute = code_block.unwrap();
-    let language_lower = language.to_lowercase();
-
-    // Send initial response
-    let initial_embed = CreateEmbed::new()
-        .title(format!("🔄 Executing {language} code"))
-        .description(format!("```{language}\n{code_to_execute}\n```"))
-        .color(0xFFFF00); // Yellow for "running"
-
-    let reply = ctx
-        .send(CreateReply::default().embed(initial_embed))
-        .await?;
-
-    // Execute the code
-    let execution_result = DockerExecutor::new()
-        .execute(&language_lower, &code_to_execute)
-        .await;
-
-    // Prepare the result embed
-    let result_embed = match execution_result {
-        Ok(result) => {
-            let status_emoji = if result.exit_code == Some(0) && !result.timed_out {
-                "✅"
-            } else if result.timed_out {
-                "⏰"
-            } else {
-                "❌"
-            };
+    compiler.check_rate_limit(&origin(ctx))?;
 
-            let color = if result.exit_code == Some(0) && !result.timed_out {
-                0x00FF00 // Green for success
-            } else if result.timed_out {
-                0xFF8800 // Orange for timeout
-            } else {
-                0xFF0000 // Red for error
-            };
-
-            let mut embed = CreateEmbed::new()
-                .title(format!("{status_emoji} Execution result"))
-                .color(color);
-
-            // Add stdout if present
-            if !result.stdout.is_empty() {
-                let max_len = DockerExecutor::new().output.max_output_length;
-                let stdout_content = if result.stdout.len() > max_len {
-                    format!(
-                        "{}{}",
-                        &result.stdout[..max_len],
-                        DockerExecutor::new().output.truncate_suffix
-                    )
-                } else {
-                    result.stdout.clone()
-                };
-                embed = embed.field("Output", format!("```\n{stdout_content}\n```"), false);
-            }
+    Ok(true)
+}
 
-            // Add stderr if present
-            if !result.stderr.is_empty() {
-                let max_len = DockerExecutor::new().output.max_output_length;
-                let stderr_content = if result.stderr.len() > max_len {
-                    format!(
-                        "{}{}",
-                        &result.stderr[..max_len],
-                        DockerExecutor::new().output.truncate_suffix
-                    )
-                } else {
-                    result.stderr.clone()
-                };
-                embed = embed.field("Error", format!("```\n{stderr_content}\n```"), false);
-            }
+/// Refuses the command once the user or guild has used up its CPU time quota.
+async fn check_quota(ctx: CompilerBotContext<'_>) -> Result<bool, CompilerBotError> {
+    ctx.data().compiler.check_quota(&origin(ctx))?;
 
-            // Add execution info
-            let mut execution_info = String::new();
-            if let Some(code) = result.exit_code {
-                execution_info.push_str(&format!("Exit code: {code}\n"));
-            }
-            if result.timed_out {
-                execution_info.push_str("⚠️ Execution timed out\n");
-            }
-            if !execution_info.is_empty() && result.exit_code != Some(0) {
-                embed = embed.field("Execution Info", execution_info, true);
-            }
+    Ok(true)
+}
 
-            embed
+fn origin(ctx: CompilerBotContext<'_>) -> Origin {
+    Origin {
+        user: ctx.author().id.get(),
+        channel: ctx.channel_id().get(),
+        guild: ctx.guild_id().map(|guild| guild.get()),
+    }
+}
+
+#[async_trait]
+impl compiler::Attachment for Attachment {
+    fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    async fn download(&self) -> Result<Vec<u8>, String> {
+        Attachment::download(self).await.map_err(|e| e.to_string())
+    }
+}
+
+/// Shows reports as an embed, sent with the first one and edited after.
+struct EmbedReply<'a> {
+    ctx: CompilerBotContext<'a>,
+    handle: Option<ReplyHandle<'a>>,
+}
+
+#[async_trait]
+impl Reply for EmbedReply<'_> {
+    async fn show(&mut self, report: Report, files: Vec<OutputFile>) -> Result<(), String> {
+        let mut embed = embed(report);
+
+        // Attach output files, showing the first image inline
+        let mut attachments = Vec::new();
+        let mut image_shown = false;
+        for file in files {
+            let filename = attachment_filename(&file.name);
+            if !image_shown && is_image(&filename) {
+                embed = embed.image(format!("attachment://{filename}"));
+                image_shown = true;
+            }
+            attachments.push(CreateAttachment::bytes(file.contents, filename));
         }
-        Err(error) => {
-            CreateEmbed::new()
-                .title("❌ Execution failed")
-                .description(format!("Failed to execute {language} code"))
-                .field("Error", format!("```\n{error}\n```"), false)
-                .field(
-                    "Source Code",
-                    format!("```{language}\n{code_to_execute}\n```"),
-                    false,
-                )
-                .color(0xFF0000) // Red for error
+
+        let reply = attachments
+            .into_iter()
+            .fold(CreateReply::default().embed(embed), |reply, attachment| {
+                reply.attachment(attachment)
+            });
+        match &self.handle {
+            Some(handle) => handle.edit(self.ctx, reply).await,
+            None => self
+                .ctx
+                .send(reply)
+                .await
+                .map(|handle| self.handle = Some(handle)),
         }
-    };
+        .map_err(|e| format!("Failed to send reply: {e}"))
+    }
+}
 
-    // Update the message with the result
-    reply
-        .edit(ctx, CreateReply::default().embed(result_embed))
-        .await?;
+/// Lays the report out as an embed, with code and output in code blocks.
+fn embed(report: Report) -> CreateEmbed {
+    let mut embed = CreateEmbed::new().title(report.title).color(report.color);
+    if let Some(description) = report.description {
+        embed = embed.description(description.to_markdown());
+    }
+    for field in report.fields {
+        embed = embed.field(field.name, field.value.to_markdown(), field.inline);
+    }
+    if let Some(footer) = report.footer {
+        embed = embed.footer(CreateEmbedFooter::new(footer));
+    }
 
-    Ok(())
+    embed
+}
+
+/// Flattens an output file path into a name Discord accepts and can reference from an embed.
+fn attachment_filename(path: &str) -> String {
+    path.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
@@ -18,7 +18,7 @@
 
 use poise::CreateReply;
 
-use crate::{CompilerBotContext, CompilerBotError, docker_executor::DockerExecutor, prelude::*};
+use crate::{CompilerBotContext, CompilerBotError, prelude::*};
 
 /// Show this help menu
 #[poise::command(prefix_command, slash_command)]
@@ -41,7 +41,7 @@ pub async fn help(
 /// List supported languages for compilation
 #[poise::command(prefix_command, slash_command)]
 pub async fn languages(ctx: CompilerBotContext<'_>) -> Result<(), CompilerBotError> {
-    let supported_languages = DockerExecutor::new().supported_languages();
+    let supported_languages = ctx.data().compiler.executor.supported_languages();
 
     let language_list = supported_languages
         .iter()
@@ -18,3 +18,4 @@
 
 pub mod compile;
 pub mod info;
+pub mod quota;
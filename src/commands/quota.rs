@@ -0,0 +1,135 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::time::Duration;
+
+use poise::CreateReply;
+
+use crate::{
+    CompilerBotContext, CompilerBotError,
+    prelude::*,
+    quota::{Account, QuotaStatus, format_duration},
+};
+
+/// Show how much CPU time you and this server have left
+#[poise::command(prefix_command, slash_command, subcommands("reset", "set"))]
+pub async fn quota(ctx: CompilerBotContext<'_>) -> Result<(), CompilerBotError> {
+    let quotas = &ctx.data().compiler.quotas;
+
+    let mut embed = CreateEmbed::new().title("CPU time quota").field(
+        "You",
+        describe(&quotas.status(Account::User(ctx.author().id.get()))),
+        false,
+    );
+    if let Some(guild) = ctx.guild_id() {
+        embed = embed.field(
+            "This server",
+            describe(&quotas.status(Account::Guild(guild.get()))),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Clear the CPU time used by this server, or by a user (bot owners only)
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn reset(
+    ctx: CompilerBotContext<'_>,
+    #[description = "The user to reset"] user: Option<User>,
+) -> Result<(), CompilerBotError> {
+    let (account, target) = account_for(ctx, user.as_ref())?;
+    ctx.data().compiler.quotas.reset(account);
+    ctx.say(format!("Reset the CPU time used by {target}"))
+        .await?;
+
+    Ok(())
+}
+
+/// Set the CPU seconds this server, or a user (bot owners only), may use until the bot restarts
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn set(
+    ctx: CompilerBotContext<'_>,
+    #[description = "CPU seconds allowed per window"] cpu_seconds: u64,
+    #[description = "The user to set the quota of"] user: Option<User>,
+) -> Result<(), CompilerBotError> {
+    let (account, target) = account_for(ctx, user.as_ref())?;
+    ctx.data()
+        .compiler
+        .quotas
+        .set_limit(account, Duration::from_secs(cpu_seconds));
+    ctx.say(format!(
+        "Set the CPU time quota of {target} to {cpu_seconds}s"
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Picks the user's account, or the guild's if no user is given, along with how to refer to it.
+/// A user's account is charged in every server, so only the bot's owners may pick one.
+fn account_for(
+    ctx: CompilerBotContext<'_>,
+    user: Option<&User>,
+) -> Result<(Account, String), CompilerBotError> {
+    match user {
+        Some(_) if !ctx.framework().options().owners.contains(&ctx.author().id) => Err(
+            "Only the bot's owners can change a user's quota, which applies in every server".into(),
+        ),
+        Some(user) => Ok((Account::User(user.id.get()), user.mention().to_string())),
+        None => {
+            let guild = ctx
+                .guild_id()
+                .ok_or("This command only works in a server")?;
+            Ok((Account::Guild(guild.get()), "this server".into()))
+        }
+    }
+}
+
+fn describe(status: &QuotaStatus) -> String {
+    let used = status.used.as_secs_f64();
+    let mut description = match (status.limit, status.remaining()) {
+        (Some(limit), Some(remaining)) => format!(
+            "{:.1}s left, {used:.1}s of {}s used",
+            remaining.as_secs_f64(),
+            limit.as_secs()
+        ),
+        _ => format!("{used:.1}s used, no limit"),
+    };
+
+    if let Some(frees_up_in) = status.frees_up_in {
+        description.push_str(&format!(
+            "\nOldest usage expires in {}",
+            format_duration(frees_up_in)
+        ));
+    }
+
+    description
+}
@@ -0,0 +1,495 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{path::Path, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::{InputConfig, OutputConfig},
+    executors::{ExecutionRequest, Executor, OutputFile, SourceFile},
+    quota::{QuotaExceeded, QuotaTracker},
+    rate_limit::{RateLimited, RateLimiter},
+    render::Report,
+    runners::language_for_extension,
+    scheduler::Scheduler,
+    utils::{extract_code_blocks, extract_zip, has_extension, parse_file_label},
+};
+
+/// Everything needed to turn a chat message into a run and its result, shared by every chat
+/// platform the bot is on. Platforms supply the message's text and attachments, and a [`Reply`]
+/// to show progress and the result in.
+pub struct Compiler {
+    pub executor: Arc<dyn Executor>,
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub scheduler: Arc<Scheduler>,
+    pub rate_limiter: RateLimiter,
+    pub quotas: QuotaTracker,
+}
+
+/// Who asked for a job and where, for rate limits, quotas and fair queueing. Platforms whose IDs
+/// aren't numbers hash them.
+#[derive(Clone, Copy, Debug)]
+pub struct Origin {
+    pub user: u64,
+    pub channel: u64,
+    pub guild: Option<u64>,
+}
+
+/// A file attached to a message.
+#[async_trait]
+pub trait Attachment: Sync {
+    fn filename(&self) -> &str;
+
+    /// Size in bytes as reported by the platform, checked before downloading.
+    fn size(&self) -> usize;
+
+    async fn download(&self) -> Result<Vec<u8>, String>;
+}
+
+/// Message a job's progress and then its result are shown in.
+#[async_trait]
+pub trait Reply: Send {
+    /// Shows the report in place of the last one, along with the files the program wrote.
+    async fn show(&mut self, report: Report, files: Vec<OutputFile>) -> Result<(), String>;
+}
+
+/// A request parsed from a message, ready to run.
+pub struct Job {
+    /// The language as the user wrote it, for display.
+    pub language: String,
+    pub request: ExecutionRequest,
+}
+
+impl Compiler {
+    /// Takes a token from the origin's rate limit buckets. Platforms skip this for users they
+    /// exempt.
+    pub fn check_rate_limit(&self, origin: &Origin) -> Result<(), RateLimited> {
+        self.rate_limiter
+            .check(origin.user, origin.channel, origin.guild)
+    }
+
+    pub fn check_quota(&self, origin: &Origin) -> Result<(), QuotaExceeded> {
+        self.quotas.check(origin.user, origin.guild)
+    }
+
+    /// Gathers the files to run from the message's code blocks, falling back to an attached zip
+    /// archive or source file, along with stdin from an attached `.txt` file. The language is
+    /// inferred from an attachment's extension unless given.
+    pub async fn parse(
+        &self,
+        language: Option<String>,
+        code: Option<&str>,
+        attachments: &[&dyn Attachment],
+    ) -> Result<Job, String> {
+        let input = &self.input;
+        let (files, inferred_language) = collect_source_files(code, attachments, input).await?;
+
+        let language = language
+            .or_else(|| inferred_language.map(String::from))
+            .ok_or("No language specified")?;
+
+        let stdin = match attachments
+            .iter()
+            .find(|attachment| has_extension(attachment.filename(), "txt"))
+        {
+            Some(attachment) => read_attachment(*attachment, input.max_stdin_size).await?,
+            None => String::new(),
+        };
+
+        Ok(Job {
+            request: ExecutionRequest {
+                language: language.to_lowercase(),
+                files,
+                stdin,
+            },
+            language,
+        })
+    }
+
+    /// Queues the job and runs it once its turn comes, showing where it is in the queue
+    /// meanwhile, then charges its CPU time and shows the result.
+    pub async fn run(
+        &self,
+        origin: &Origin,
+        job: Job,
+        reply: &mut dyn Reply,
+    ) -> Result<(), String> {
+        let Job { language, request } = job;
+        let output = &self.output;
+
+        // Wait for a turn to run, showing where the job is in the queue meanwhile
+        let mut ticket = self.scheduler.enqueue(origin.user, &request.language);
+        let mut position = ticket.position();
+        let pending = |position| Report::pending(&request.files, &language, position, output);
+
+        reply.show(pending(position), Vec::new()).await?;
+        while position > 0 {
+            position = ticket.position_changed().await;
+            reply.show(pending(position), Vec::new()).await?;
+        }
+
+        // Execute the code, keeping the ticket until it finishes
+        let execution_result = self.executor.execute(&request).await;
+        drop(ticket);
+
+        match execution_result {
+            Ok(result) => {
+                let usage = &result.usage;
+                self.quotas.record(
+                    origin.user,
+                    origin.guild,
+                    usage.cpu_time.unwrap_or(usage.wall_time),
+                );
+
+                let report = Report::result(&result, output);
+                reply.show(report, result.files).await
+            }
+            Err(error) => {
+                let report = Report::failure(&error, &request.files, &language, output);
+                reply.show(report, Vec::new()).await
+            }
+        }
+    }
+}
+
+/// Gathers the files to run from the message's code blocks, falling back to an attached zip
+/// archive or source file. Also returns the language implied by an attachment's extension.
+async fn collect_source_files(
+    code: Option<&str>,
+    attachments: &[&dyn Attachment],
+    input: &InputConfig,
+) -> Result<(Vec<SourceFile>, Option<&'static str>), String> {
+    let code_blocks = code.map(extract_code_blocks).unwrap_or_default();
+    if !code_blocks.is_empty() {
+        return Ok((files_from_code_blocks(&code_blocks)?, None));
+    }
+
+    if let Some(archive) = attachments
+        .iter()
+        .find(|attachment| has_extension(attachment.filename(), "zip"))
+    {
+        let bytes = download_attachment(*archive, input.max_source_size).await?;
+        let files = extract_zip(&bytes, input)?;
+        let language = files.iter().find_map(|file| language_of(&file.name));
+        return Ok((files, language));
+    }
+
+    if let Some((attachment, language)) = attachments
+        .iter()
+        .find_map(|attachment| Some((*attachment, language_of(attachment.filename())?)))
+    {
+        let contents = read_attachment(attachment, input.max_source_size).await?;
+        let file = SourceFile {
+            name: attachment.filename().into(),
+            contents,
+        };
+        return Ok((vec![file], Some(language)));
+    }
+
+    Err("No code block or source file found".into())
+}
+
+fn files_from_code_blocks(code_blocks: &[String]) -> Result<Vec<SourceFile>, String> {
+    if let [code_block] = code_blocks {
+        let (name, contents) = parse_file_label(code_block);
+        return Ok(vec![SourceFile {
+            name: name.unwrap_or_default(),
+            contents,
+        }]);
+    }
+
+    code_blocks
+        .iter()
+        .map(|code_block| match parse_file_label(code_block) {
+            (Some(name), contents) => Ok(SourceFile { name, contents }),
+            (None, _) => Err(
+                "Label each code block with a `// file: <name>` first line to send several files"
+                    .into(),
+            ),
+        })
+        .collect()
+}
+
+fn language_of(filename: &str) -> Option<&'static str> {
+    let extension = Path::new(filename).extension()?.to_str()?;
+    language_for_extension(extension).map(|language| language.name())
+}
+
+async fn download_attachment(
+    attachment: &dyn Attachment,
+    max_size: usize,
+) -> Result<Vec<u8>, String> {
+    if attachment.size() > max_size {
+        return Err(format!(
+            "`{}` is larger than the {max_size} byte limit",
+            attachment.filename()
+        ));
+    }
+
+    attachment
+        .download()
+        .await
+        .map_err(|e| format!("Failed to download `{}`: {e}", attachment.filename()))
+}
+
+async fn read_attachment(attachment: &dyn Attachment, max_size: usize) -> Result<String, String> {
+    let bytes = download_attachment(attachment, max_size).await?;
+    String::from_utf8(bytes).map_err(|_| format!("`{}` is not valid UTF-8", attachment.filename()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        config::{QuotaConfig, RateLimitConfig, SchedulerConfig},
+        executors::{ExecutionResult, ExecutionStatus, MockExecutor, ResourceUsage},
+        quota::Account,
+    };
+
+    const ORIGIN: Origin = Origin {
+        user: 1,
+        channel: 2,
+        guild: Some(3),
+    };
+
+    fn compiler(executor: Arc<MockExecutor>) -> Compiler {
+        Compiler {
+            executor,
+            input: InputConfig::default(),
+            output: OutputConfig::default(),
+            scheduler: Arc::new(Scheduler::new(SchedulerConfig::default())),
+            rate_limiter: RateLimiter::new(RateLimitConfig::default()),
+            quotas: QuotaTracker::new(QuotaConfig::default()),
+        }
+    }
+
+    fn python_job(code: &str) -> Job {
+        Job {
+            language: "Python".into(),
+            request: ExecutionRequest {
+                language: "python".into(),
+                files: vec![SourceFile {
+                    name: String::new(),
+                    contents: code.into(),
+                }],
+                stdin: String::new(),
+            },
+        }
+    }
+
+    /// Keeps every report shown, in order.
+    #[derive(Default)]
+    struct Recorder {
+        reports: Vec<Report>,
+    }
+
+    #[async_trait]
+    impl Reply for Recorder {
+        async fn show(&mut self, report: Report, _files: Vec<OutputFile>) -> Result<(), String> {
+            self.reports.push(report);
+            Ok(())
+        }
+    }
+
+    struct File {
+        name: &'static str,
+        contents: &'static str,
+    }
+
+    #[async_trait]
+    impl Attachment for File {
+        fn filename(&self) -> &str {
+            self.name
+        }
+
+        fn size(&self) -> usize {
+            self.contents.len()
+        }
+
+        async fn download(&self) -> Result<Vec<u8>, String> {
+            Ok(self.contents.as_bytes().to_vec())
+        }
+    }
+
+    #[tokio::test]
+    async fn run_shows_the_result_and_charges_cpu_time() {
+        let executor = Arc::new(MockExecutor::scripted([Ok(ExecutionResult {
+            stdout: "hello\n".into(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            status: ExecutionStatus::Completed,
+            files: Vec::new(),
+            usage: ResourceUsage {
+                wall_time: Duration::from_secs(2),
+                cpu_time: Some(Duration::from_secs(1)),
+                peak_memory: None,
+            },
+        })]));
+        let compiler = compiler(executor.clone());
+        let mut reply = Recorder::default();
+
+        compiler
+            .run(&ORIGIN, python_job("print('hello')"), &mut reply)
+            .await
+            .unwrap();
+
+        let titles = reply
+            .reports
+            .iter()
+            .map(|report| report.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["🔄 Executing Python code", "✅ Execution result"]);
+        assert!(reply.reports[1].to_plain_text().contains("hello"));
+        assert_eq!(executor.requests()[0].files[0].contents, "print('hello')");
+
+        let used = compiler.quotas.status(Account::User(1)).used;
+        assert_eq!(used, Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn run_reports_timeouts() {
+        let executor = Arc::new(MockExecutor::scripted([Ok(ExecutionResult {
+            stdout: "partial".into(),
+            stderr: String::new(),
+            exit_code: Some(124),
+            status: ExecutionStatus::TimedOut,
+            files: Vec::new(),
+            usage: ResourceUsage {
+                wall_time: Duration::from_secs(300),
+                ..Default::default()
+            },
+        })]));
+        let compiler = compiler(executor);
+        let mut reply = Recorder::default();
+
+        compiler
+            .run(&ORIGIN, python_job("while True: pass"), &mut reply)
+            .await
+            .unwrap();
+
+        let report = reply.reports.last().unwrap();
+        assert_eq!(report.title, "⏰ Execution timed out");
+        assert!(report.to_plain_text().contains("partial"));
+
+        // Wall time is charged when CPU time is unknown
+        let used = compiler.quotas.status(Account::Guild(3)).used;
+        assert_eq!(used, Duration::from_secs(300));
+    }
+
+    #[tokio::test]
+    async fn run_reports_executor_errors() {
+        let executor = Arc::new(MockExecutor::scripted([Err(
+            "Failed to spawn Docker process".into(),
+        )]));
+        let compiler = compiler(executor);
+        let mut reply = Recorder::default();
+
+        compiler
+            .run(&ORIGIN, python_job("print(1)"), &mut reply)
+            .await
+            .unwrap();
+
+        let report = reply.reports.last().unwrap();
+        assert_eq!(report.title, "❌ Execution failed");
+        assert!(
+            report
+                .to_plain_text()
+                .contains("Failed to spawn Docker process")
+        );
+        let used = compiler.quotas.status(Account::User(1)).used;
+        assert_eq!(used, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn parse_reads_code_blocks() {
+        let compiler = compiler(Arc::new(MockExecutor::new()));
+
+        let job = compiler
+            .parse(Some("Python".into()), Some("```py\nprint(1)\n```"), &[])
+            .await
+            .unwrap();
+        assert_eq!(job.language, "Python");
+        assert_eq!(job.request.language, "python");
+        assert_eq!(job.request.files[0].contents, "print(1)");
+
+        let labeled = "```\n// file: main.cpp\nint main() {}\n```\n```\n// file: util.h\n```";
+        let job = compiler
+            .parse(Some("cpp".into()), Some(labeled), &[])
+            .await
+            .unwrap();
+        let names = job
+            .request
+            .files
+            .iter()
+            .map(|file| file.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["main.cpp", "util.h"]);
+    }
+
+    #[tokio::test]
+    async fn parse_infers_the_language_from_attachments() {
+        let compiler = compiler(Arc::new(MockExecutor::new()));
+        let source = File {
+            name: "solution.py",
+            contents: "print(input())",
+        };
+        let stdin = File {
+            name: "input.txt",
+            contents: "42",
+        };
+
+        let job = compiler
+            .parse(None, None, &[&source, &stdin])
+            .await
+            .unwrap();
+        assert_eq!(job.request.language, "python");
+        assert_eq!(job.request.files[0].contents, "print(input())");
+        assert_eq!(job.request.stdin, "42");
+    }
+
+    #[tokio::test]
+    async fn parse_refuses_messages_without_code() {
+        let compiler = compiler(Arc::new(MockExecutor::new()));
+
+        let error = compiler
+            .parse(Some("python".into()), Some("no code here"), &[])
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error, "No code block or source file found");
+
+        let error = compiler
+            .parse(None, Some("```\nprint(1)\n```"), &[])
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error, "No language specified");
+
+        let unlabeled = "```\nprint(1)\n```\n```\nprint(2)\n```";
+        assert!(
+            compiler
+                .parse(Some("python".into()), Some(unlabeled), &[])
+                .await
+                .is_err()
+        );
+    }
+}
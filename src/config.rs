@@ -16,8 +16,53 @@
  * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::{collections::HashMap, env, fs, io::ErrorKind};
+
 use serde::{Deserialize, Serialize};
 
+use crate::runners::Language;
+
+/// Value of [`SecurityConfig::seccomp_profile`] and [`NsjailConfig::seccomp_policy`] that picks
+/// the profile built into the bot, so it doesn't depend on the directory the bot runs from.
+pub const BUILTIN_SECCOMP: &str = "builtin";
+
+/// Settings read from the TOML file at `CONFIG_PATH`, or `config.toml` when that is unset.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotConfig {
+    pub backend: Backend,
+    pub languages: LanguageOverrides,
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub pool: PoolConfig,
+    pub scheduler: SchedulerConfig,
+    pub rate_limit: RateLimitConfig,
+    pub quota: QuotaConfig,
+    pub docker_api: DockerApiConfig,
+    pub nsjail: NsjailConfig,
+    pub wasi: WasiConfig,
+    pub remote: RemoteConfig,
+    pub worker: WorkerConfig,
+    pub piston: PistonConfig,
+    pub api: ApiConfig,
+    pub matrix: MatrixConfig,
+}
+
+impl BotConfig {
+    /// Loads the configuration file, falling back to the defaults if there is none.
+    pub fn load() -> Result<Self, String> {
+        let path = env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".into());
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str(&contents).map_err(|e| format!("Invalid config file {path}: {e}"))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read config file {path}: {e}")),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub cpu_limit: String,
@@ -25,7 +70,44 @@ pub struct SecurityConfig {
     pub pids_limit: u32,
     pub file_descriptor_limit: String,
     pub disable_network: bool,
+    /// Network joined when `disable_network` is off, such as an internal-only bridge made with
+    /// `docker network create --internal` for database runners. Docker's default bridge is
+    /// used when unset.
+    pub network: Option<String>,
+    pub no_new_privileges: bool,
+    /// Mounts the root filesystem read-only, with tmpfs mounts for `/tmp` and the working
+    /// directory and a volume for the output directory.
+    pub read_only_rootfs: bool,
+    /// Size of each tmpfs mount made when the root filesystem is read-only.
+    pub tmpfs_size: String,
+    /// User to run as inside the container instead of the image's default.
+    pub user: Option<String>,
+    pub cap_add: Vec<String>,
+    pub cap_drop: Vec<String>,
+    /// Path to a seccomp profile replacing Docker's default one, or [`BUILTIN_SECCOMP`] for the
+    /// one from `docker/seccomp.json` built into the bot.
+    pub seccomp_profile: Option<String>,
+    /// Container runtime to use instead of Docker's default, such as `runsc` for gVisor.
+    pub runtime: Option<String>,
     pub timeout_duration: u64,
+    /// Seconds a timed out program gets to exit after SIGTERM before it is killed.
+    pub termination_grace_period: u64,
+}
+
+impl SecurityConfig {
+    /// Locked down profile that runs as the images' unprivileged `sandbox` user on a read-only
+    /// root filesystem, with every capability dropped and the built-in seccomp profile, which
+    /// only allows the system calls programs commonly need and refuses `ptrace` and creating
+    /// namespaces.
+    pub fn hardened() -> Self {
+        Self {
+            read_only_rootfs: true,
+            user: Some("10001:10001".into()),
+            cap_drop: vec!["ALL".into()],
+            seccomp_profile: Some(BUILTIN_SECCOMP.into()),
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for SecurityConfig {
@@ -36,15 +118,124 @@ impl Default for SecurityConfig {
             pids_limit: 100,
             file_descriptor_limit: "64:64".into(),
             disable_network: true,
+            network: None,
+            no_new_privileges: true,
+            read_only_rootfs: false,
+            tmpfs_size: "64m".into(),
+            user: None,
+            cap_add: Vec::new(),
+            cap_drop: Vec::new(),
+            seccomp_profile: None,
+            runtime: None,
             timeout_duration: 300,
+            termination_grace_period: 5,
+        }
+    }
+}
+
+/// Changes to the runners' built-in security settings, keyed by language name, such as
+/// `[languages.python]` with `runtime = "runsc"` to run Python under gVisor.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LanguageOverrides(HashMap<String, SecurityOverrides>);
+
+impl LanguageOverrides {
+    /// The settings the language runs with, its runner's own with any overrides applied.
+    pub fn security_config(&self, language: &(dyn Language + Send + Sync)) -> SecurityConfig {
+        let security = language.security_config();
+        match self.0.get(language.name()) {
+            Some(overrides) => overrides.apply(security),
+            None => security,
         }
     }
 }
 
+/// Fields of [`SecurityConfig`] to change for a language. Unset fields keep the runner's value.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityOverrides {
+    pub cpu_limit: Option<String>,
+    pub memory_limit: Option<String>,
+    pub pids_limit: Option<u32>,
+    pub file_descriptor_limit: Option<String>,
+    pub disable_network: Option<bool>,
+    pub network: Option<String>,
+    pub no_new_privileges: Option<bool>,
+    pub read_only_rootfs: Option<bool>,
+    pub tmpfs_size: Option<String>,
+    pub user: Option<String>,
+    pub cap_add: Option<Vec<String>>,
+    pub cap_drop: Option<Vec<String>>,
+    pub seccomp_profile: Option<String>,
+    pub runtime: Option<String>,
+    pub timeout_duration: Option<u64>,
+    pub termination_grace_period: Option<u64>,
+}
+
+impl SecurityOverrides {
+    fn apply(&self, security: SecurityConfig) -> SecurityConfig {
+        let overrides = self.clone();
+        SecurityConfig {
+            cpu_limit: overrides.cpu_limit.unwrap_or(security.cpu_limit),
+            memory_limit: overrides.memory_limit.unwrap_or(security.memory_limit),
+            pids_limit: overrides.pids_limit.unwrap_or(security.pids_limit),
+            file_descriptor_limit: overrides
+                .file_descriptor_limit
+                .unwrap_or(security.file_descriptor_limit),
+            disable_network: overrides
+                .disable_network
+                .unwrap_or(security.disable_network),
+            network: overrides.network.or(security.network),
+            no_new_privileges: overrides
+                .no_new_privileges
+                .unwrap_or(security.no_new_privileges),
+            read_only_rootfs: overrides
+                .read_only_rootfs
+                .unwrap_or(security.read_only_rootfs),
+            tmpfs_size: overrides.tmpfs_size.unwrap_or(security.tmpfs_size),
+            user: overrides.user.or(security.user),
+            cap_add: overrides.cap_add.unwrap_or(security.cap_add),
+            cap_drop: overrides.cap_drop.unwrap_or(security.cap_drop),
+            seccomp_profile: overrides.seccomp_profile.or(security.seccomp_profile),
+            runtime: overrides.runtime.or(security.runtime),
+            timeout_duration: overrides
+                .timeout_duration
+                .unwrap_or(security.timeout_duration),
+            termination_grace_period: overrides
+                .termination_grace_period
+                .unwrap_or(security.termination_grace_period),
+        }
+    }
+}
+
+/// Which executor runs submitted code.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    Docker,
+    /// Docker through the Engine API on its Unix socket rather than the CLI.
+    DockerApi,
+    /// Podman, which can run rootless on hosts where Docker isn't available.
+    Podman,
+    /// nsjail on the host's own toolchains, for hosts without a container engine.
+    Nsjail,
+    /// A self-hosted Piston instance.
+    Piston,
+    /// Worker processes started with `compiler-bot worker`, usually on other machines.
+    Remote,
+    /// Runs nothing and echoes the input back, for trying the bot out without a sandbox.
+    Mock,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OutputConfig {
     pub max_output_length: usize,
     pub truncate_suffix: String,
+    pub max_capture_size: usize,
+    pub max_files: usize,
+    pub max_files_size: usize,
 }
 
 impl Default for OutputConfig {
@@ -52,6 +243,342 @@ impl Default for OutputConfig {
         Self {
             max_output_length: 1000,
             truncate_suffix: "...\n(truncated)".into(),
+            max_capture_size: 256 * 1024,
+            max_files: 10,
+            max_files_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    pub max_files: usize,
+    pub max_source_size: usize,
+    pub max_stdin_size: usize,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            max_files: 32,
+            max_source_size: 64 * 1024,
+            max_stdin_size: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Idle containers kept started for each language so runs skip container startup, or 0 to
+    /// start a fresh container for every run.
+    pub size: usize,
+    /// Seconds to wait before trying again after a pool container failed to start.
+    pub retry_interval: u64,
+    /// Seconds a pool container waits for a run before stopping itself, so ones left behind by
+    /// a bot that crashed go away. Containers are replaced before a run could outlast them.
+    pub max_idle: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            retry_interval: 30,
+            max_idle: 3600,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Jobs allowed to run at once across all languages.
+    pub max_jobs: usize,
+    /// Jobs allowed to run at once for a language not listed in `language_limits`.
+    pub max_jobs_per_language: usize,
+    /// Per-language overrides of `max_jobs_per_language`, keyed by language name.
+    pub language_limits: HashMap<String, usize>,
+}
+
+impl SchedulerConfig {
+    pub fn language_limit(&self, language: &str) -> usize {
+        self.language_limits
+            .get(language)
+            .copied()
+            .unwrap_or(self.max_jobs_per_language)
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_jobs: 4,
+            max_jobs_per_language: 2,
+            language_limits: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub user: BucketConfig,
+    pub channel: BucketConfig,
+    pub guild: BucketConfig,
+    /// Limit for each key of the HTTP API.
+    pub api_key: BucketConfig,
+    /// IDs of roles whose members are never rate limited.
+    pub exempt_roles: Vec<u64>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            user: BucketConfig {
+                capacity: 5,
+                refill_interval: 12,
+            },
+            channel: BucketConfig {
+                capacity: 10,
+                refill_interval: 6,
+            },
+            guild: BucketConfig {
+                capacity: 30,
+                refill_interval: 2,
+            },
+            api_key: BucketConfig {
+                capacity: 10,
+                refill_interval: 6,
+            },
+            exempt_roles: Vec::new(),
+        }
+    }
+}
+
+/// Token bucket allowing a burst of `capacity` jobs, then one more every `refill_interval`
+/// seconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BucketConfig {
+    /// Most jobs allowed in a burst, or 0 for no limit.
+    pub capacity: u32,
+    pub refill_interval: u64,
+}
+
+impl Default for BucketConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 5,
+            refill_interval: 12,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// CPU seconds each user may use per window, or 0 for no limit.
+    pub user_cpu_seconds: u64,
+    /// CPU seconds each guild may use per window, or 0 for no limit.
+    pub guild_cpu_seconds: u64,
+    /// Length in seconds of the rolling window usage is counted over.
+    pub window: u64,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            user_cpu_seconds: 600,
+            guild_cpu_seconds: 7200,
+            window: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DockerApiConfig {
+    /// Unix socket the Docker daemon serves the Engine API on.
+    pub socket: String,
+}
+
+impl Default for DockerApiConfig {
+    fn default() -> Self {
+        Self {
+            socket: "/var/run/docker.sock".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NsjailConfig {
+    /// Path to the nsjail binary.
+    pub path: String,
+    /// Cgroup v2 directory delegated to the bot, which each job gets a child cgroup of.
+    pub cgroup_root: String,
+    /// Directory each job's output directory is made in on the host.
+    pub work_dir: String,
+    /// Host directories mounted read-only in the sandbox, skipped when they don't exist.
+    pub bind_mounts: Vec<String>,
+    /// Kafel seccomp policy file applied to the sandbox, [`BUILTIN_SECCOMP`] for the one from
+    /// `nsjail/seccomp.kafel` built into the bot, or unset for none.
+    pub seccomp_policy: Option<String>,
+}
+
+impl Default for NsjailConfig {
+    fn default() -> Self {
+        Self {
+            path: "nsjail".into(),
+            cgroup_root: "/sys/fs/cgroup/compiler-bot".into(),
+            work_dir: "/tmp/compiler-bot".into(),
+            bind_mounts: ["/bin", "/etc", "/lib", "/lib64", "/opt", "/sbin", "/usr"]
+                .map(String::from)
+                .into(),
+            seccomp_policy: Some(BUILTIN_SECCOMP.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WasiConfig {
+    /// WASI build of CPython that plain Python snippets are run in instead of the backend, or
+    /// unset to run every snippet in the backend. Needs the `wasi` feature.
+    pub python: Option<String>,
+    /// Host directory holding the interpreter's standard library, mounted read-only at
+    /// `/usr/local/lib`, for builds that don't embed it.
+    pub python_lib: Option<String>,
+    /// Fuel, roughly the WebAssembly instructions executed, a run may use before it is stopped,
+    /// or 0 to only stop it at the language's timeout.
+    pub fuel: u64,
+    /// Directory each run's files are written to on the host.
+    pub work_dir: String,
+}
+
+impl Default for WasiConfig {
+    fn default() -> Self {
+        Self {
+            python: None,
+            python_lib: None,
+            fuel: 0,
+            work_dir: "/tmp/compiler-bot-wasi".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Base URLs of the workers jobs are spread across, such as `http://10.0.0.2:8081`.
+    pub workers: Vec<String>,
+    /// Seconds between health checks of each worker.
+    pub health_check_interval: u64,
+    /// Seconds a worker has to finish a job before it is given up on.
+    pub request_timeout: u64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            workers: Vec::new(),
+            health_check_interval: 10,
+            request_timeout: 600,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerConfig {
+    /// Address `compiler-bot worker` serves the job API on.
+    pub listen: String,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:8081".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PistonConfig {
+    /// Base URL of the Piston instance, without the `/api/v2` path.
+    pub url: String,
+    /// Sent as the `Authorization` header, for instances behind a key.
+    pub api_key: Option<String>,
+    /// Piston's name for each of our languages whose name differs, keyed by our name.
+    pub languages: HashMap<String, String>,
+    /// Longest compile stage in milliseconds the instance accepts, which languages' timeouts are
+    /// capped at. Piston refuses jobs asking for more than its `compile_timeout` setting.
+    pub max_compile_timeout: u64,
+    /// Longest run stage in milliseconds the instance accepts, from its `run_timeout` setting.
+    pub max_run_timeout: u64,
+    /// Most memory a stage may be given, such as `512m`, from the instance's memory limit
+    /// settings. Unset for instances that don't limit it, as Piston doesn't by default.
+    pub max_memory_limit: Option<String>,
+}
+
+impl Default for PistonConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:2000".into(),
+            api_key: None,
+            languages: HashMap::new(),
+            max_compile_timeout: 10000,
+            max_run_timeout: 3000,
+            max_memory_limit: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Serves the HTTP API alongside the bot, when it was built with the api feature.
+    pub enabled: bool,
+    pub listen: String,
+    /// Keys clients send as bearer tokens. Each one gets its own rate limit and only sees its
+    /// own jobs.
+    pub keys: Vec<String>,
+    /// Seconds a finished job's result is kept for before it is forgotten.
+    pub job_retention: u64,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "127.0.0.1:8080".into(),
+            keys: Vec::new(),
+            job_retention: 3600,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatrixConfig {
+    /// Connects to Matrix alongside Discord, or on its own when `BOT_TOKEN` is unset. The bot
+    /// logs in with the access token in the `MATRIX_TOKEN` environment variable.
+    pub enabled: bool,
+    /// Base URL of the homeserver, without the `/_matrix` path.
+    pub homeserver: String,
+    /// IDs of the rooms the bot answers in and joins when invited to, or every room if empty.
+    pub rooms: Vec<String>,
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            homeserver: "http://localhost:8008".into(),
+            rooms: Vec::new(),
         }
     }
 }
@@ -0,0 +1,368 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fs, io::Read, path::Path, process::ExitStatus, time::Duration};
+
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt},
+    process::Child,
+};
+
+use super::{ExecutionResult, ExecutionStatus, OutputFile, ResourceUsage};
+use crate::config::{OutputConfig, SecurityConfig};
+
+/// Something a run can be waited on through, such as the process attached to the sandbox.
+pub(super) trait Sandbox {
+    /// Waits for the run to exit. It may be called again after an earlier call was cancelled.
+    fn wait(&mut self) -> impl Future<Output = io::Result<ExitStatus>> + Send;
+}
+
+impl Sandbox for Child {
+    fn wait(&mut self) -> impl Future<Output = io::Result<ExitStatus>> + Send {
+        Child::wait(self)
+    }
+}
+
+/// How a sandboxed run ended.
+pub(super) struct Outcome {
+    exit_status: ExitStatus,
+    /// The run was killed for printing more than the capture limit allows.
+    limit_exceeded: bool,
+    timed_out: bool,
+}
+
+impl Outcome {
+    /// Builds the result from the captured output and the usage report at the end of stderr.
+    /// Whether the run was OOM killed is only looked up if it failed.
+    pub(super) async fn into_result(
+        self,
+        capture: OutputCapture,
+        marker: &str,
+        elapsed: Duration,
+        oom_killed: impl Future<Output = bool>,
+        security: &SecurityConfig,
+    ) -> ExecutionResult {
+        let mut stdout = String::from_utf8_lossy(&capture.stdout).into_owned();
+        let mut stderr = String::from_utf8_lossy(&capture.stderr).into_owned();
+        let stderr_length = stderr.len();
+        let usage =
+            ResourceUsage::split_report(&mut stderr, marker).unwrap_or_else(|| ResourceUsage {
+                wall_time: elapsed,
+                ..Default::default()
+            });
+
+        // Only now that the report is split off can output that ran into the reserve be told
+        // apart from the report itself
+        let report_length = stderr_length - stderr.len();
+        let over_limit =
+            capture.stdout.len() + capture.stderr.len() - report_length > capture.limit;
+        if over_limit {
+            stdout.truncate(stdout.floor_char_boundary(capture.limit));
+            let end = capture.limit.saturating_sub(stdout.len());
+            stderr.truncate(stderr.floor_char_boundary(end));
+        }
+
+        let status = if self.timed_out {
+            ExecutionStatus::TimedOut
+        } else if self.limit_exceeded || over_limit {
+            ExecutionStatus::OutputLimitExceeded
+        } else if self.exit_status.success() {
+            ExecutionStatus::Completed
+        } else {
+            ExecutionStatus::from_exit(self.exit_status, oom_killed.await, &security.memory_limit)
+        };
+
+        ExecutionResult {
+            stdout,
+            stderr,
+            exit_code: if self.timed_out {
+                Some(124) // Standard timeout exit code
+            } else {
+                self.exit_status.code()
+            },
+            status,
+            files: Vec::new(),
+            usage,
+        }
+    }
+}
+
+/// Waits for a sandboxed run to exit while capturing its output, using `signal` to signal
+/// everything in the sandbox. A run that floods its output is killed straight away, while one
+/// that runs out of time is sent SIGTERM and given a grace period to flush its output before it
+/// is killed.
+pub(super) async fn supervise<F: Future<Output = ()>>(
+    child: &mut impl Sandbox,
+    capture: &mut OutputCapture,
+    stdout: &mut (impl AsyncRead + Unpin),
+    stderr: &mut (impl AsyncRead + Unpin),
+    security: &SecurityConfig,
+    signal: impl Fn(&'static str) -> F,
+) -> io::Result<Outcome> {
+    let timeout = Duration::from_secs(security.timeout_duration);
+    let grace_period = Duration::from_secs(security.termination_grace_period);
+
+    if let Ok(result) = tokio::time::timeout(
+        timeout,
+        wait_for_exit(child, capture, stdout, stderr, &signal),
+    )
+    .await
+    {
+        return result.map(|(exit_status, limit_exceeded)| Outcome {
+            exit_status,
+            limit_exceeded,
+            timed_out: false,
+        });
+    }
+
+    // Timeout occurred, ask the program to stop and give it a grace period to flush its output
+    // before killing it
+    signal("TERM").await;
+    let stopped = tokio::time::timeout(
+        grace_period,
+        wait_for_exit(child, capture, stdout, stderr, &signal),
+    )
+    .await;
+
+    let result = match stopped {
+        Ok(result) => result,
+        Err(_) => {
+            signal("KILL").await;
+            wait_for_exit(child, capture, stdout, stderr, &signal).await
+        }
+    };
+
+    result.map(|(exit_status, limit_exceeded)| Outcome {
+        exit_status,
+        limit_exceeded,
+        timed_out: true,
+    })
+}
+
+/// Collects the output until the run exits, killing it early if it prints more than the capture
+/// limit allows.
+async fn wait_for_exit<F: Future<Output = ()>>(
+    child: &mut impl Sandbox,
+    capture: &mut OutputCapture,
+    stdout: &mut (impl AsyncRead + Unpin),
+    stderr: &mut (impl AsyncRead + Unpin),
+    signal: &impl Fn(&'static str) -> F,
+) -> io::Result<(ExitStatus, bool)> {
+    let limit_exceeded = capture.read(stdout, stderr).await;
+    if limit_exceeded {
+        signal("KILL").await;
+        // Keep draining so the sandbox can't block writing output nobody reads
+        let (mut stdout_sink, mut stderr_sink) = (io::sink(), io::sink());
+        let _ = tokio::join!(
+            io::copy(stdout, &mut stdout_sink),
+            io::copy(stderr, &mut stderr_sink)
+        );
+    }
+
+    child.wait().await.map(|status| (status, limit_exceeded))
+}
+
+/// Room kept past the capture limit for the usage report a run writes to stderr after its
+/// output, so a program printing right up to the limit doesn't push the report out.
+const REPORT_RESERVE: usize = 256;
+
+/// Collects stdout and stderr as they are written, up to a combined byte limit. The usage report
+/// doesn't count towards the limit, which is why it is only enforced exactly once the run has
+/// ended and the report has been split off.
+pub(super) struct OutputCapture {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    limit: usize,
+}
+
+impl OutputCapture {
+    pub(super) fn new(limit: usize) -> Self {
+        Self {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Reads both streams until they close, returning early with `true` once more than the
+    /// limit and the report's reserve has been written. Anything past that is discarded.
+    async fn read(
+        &mut self,
+        stdout: &mut (impl AsyncRead + Unpin),
+        stderr: &mut (impl AsyncRead + Unpin),
+    ) -> bool {
+        let mut stdout_buffer = [0; 8192];
+        let mut stderr_buffer = [0; 8192];
+        let (mut stdout_open, mut stderr_open) = (true, true);
+
+        while stdout_open || stderr_open {
+            let remaining = self.limit + REPORT_RESERVE - self.stdout.len() - self.stderr.len();
+            let limit_exceeded = tokio::select! {
+                read = stdout.read(&mut stdout_buffer), if stdout_open => match read {
+                    Ok(0) | Err(_) => {
+                        stdout_open = false;
+                        false
+                    }
+                    Ok(n) => Self::append(&mut self.stdout, &stdout_buffer[..n], remaining),
+                },
+                read = stderr.read(&mut stderr_buffer), if stderr_open => match read {
+                    Ok(0) | Err(_) => {
+                        stderr_open = false;
+                        false
+                    }
+                    Ok(n) => Self::append(&mut self.stderr, &stderr_buffer[..n], remaining),
+                },
+            };
+
+            if limit_exceeded {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Appends as much of the data as the remaining budget allows, returning whether any of it
+    /// had to be dropped.
+    fn append(buffer: &mut Vec<u8>, data: &[u8], remaining: usize) -> bool {
+        buffer.extend_from_slice(&data[..data.len().min(remaining)]);
+        data.len() > remaining
+    }
+}
+
+/// Reads the files the program wrote to the output directory, keeping as many as fit within
+/// the configured count and size limits. Only regular files are read, so links the program
+/// made can't point outside the directory.
+pub(super) fn read_output_dir(output_dir: &Path, output: &OutputConfig) -> Vec<OutputFile> {
+    let mut files = Vec::new();
+    let mut total_size = 0;
+    let mut directories = vec![output_dir.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                directories.push(path);
+                continue;
+            }
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let Some(name) = path
+                .strip_prefix(output_dir)
+                .ok()
+                .and_then(|relative| relative.to_str())
+                .map(String::from)
+            else {
+                continue;
+            };
+
+            let size = metadata.len() as usize;
+            if files.len() >= output.max_files || total_size + size > output.max_files_size {
+                tracing::warn!("Skipping output file over the limits: {name}");
+                continue;
+            }
+
+            let mut contents = Vec::with_capacity(size);
+            let read = fs::File::open(&path)
+                .and_then(|file| file.take(size as u64).read_to_end(&mut contents));
+            if read.is_err() {
+                continue;
+            }
+
+            total_size += size;
+            files.push(OutputFile { name, contents });
+        }
+    }
+
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    const MARKER: &str = "4c7d8a4e-marker";
+
+    async fn capture(limit: usize, stdout: &[u8], stderr: &[u8]) -> ExecutionResult {
+        let mut capture = OutputCapture::new(limit);
+        let limit_exceeded = capture.read(&mut &stdout[..], &mut &stderr[..]).await;
+        let outcome = Outcome {
+            exit_status: ExitStatus::from_raw(0),
+            limit_exceeded,
+            timed_out: false,
+        };
+
+        outcome
+            .into_result(
+                capture,
+                MARKER,
+                Duration::from_secs(1),
+                async { false },
+                &SecurityConfig::default(),
+            )
+            .await
+    }
+
+    fn report() -> String {
+        format!("\n{MARKER} 1500000000 250000 1048576\n")
+    }
+
+    #[tokio::test]
+    async fn output_up_to_the_limit_keeps_its_report() {
+        let stdout = "x".repeat(100);
+        let result = capture(100, stdout.as_bytes(), report().as_bytes()).await;
+
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.stdout, stdout);
+        assert_eq!(result.stderr, "");
+        assert_eq!(result.usage.wall_time, Duration::from_millis(1500));
+        assert_eq!(result.usage.cpu_time, Some(Duration::from_millis(250)));
+        assert_eq!(result.usage.peak_memory, Some(1 << 20));
+    }
+
+    #[tokio::test]
+    async fn output_in_the_reserve_is_over_the_limit() {
+        let stderr = format!("{}{}", "e".repeat(20), report());
+        let result = capture(100, "x".repeat(90).as_bytes(), stderr.as_bytes()).await;
+
+        assert_eq!(result.status, ExecutionStatus::OutputLimitExceeded);
+        assert_eq!(result.stdout.len() + result.stderr.len(), 100);
+        assert_eq!(result.usage.wall_time, Duration::from_millis(1500));
+    }
+
+    #[tokio::test]
+    async fn floods_stop_the_capture_early() {
+        let stdout = "x".repeat(100 + REPORT_RESERVE + 1);
+        let result = capture(100, stdout.as_bytes(), b"").await;
+
+        assert_eq!(result.status, ExecutionStatus::OutputLimitExceeded);
+        assert_eq!(result.stdout.len(), 100);
+    }
+}
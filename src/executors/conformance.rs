@@ -0,0 +1,190 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Behaviour every sandboxing backend has to share, checked by running real Python programs.
+//! These need the backend's sandbox and the Python runner's image or toolchain on the host, so
+//! they are ignored by default. Run them for one backend with, for example:
+//!
+//! ```sh
+//! cargo test conformance::docker -- --ignored
+//! ```
+
+use super::{ExecutionRequest, ExecutionResult, ExecutionStatus, Executor, SourceFile};
+use crate::config::BotConfig;
+
+/// Limits kept small so the tests finish quickly.
+const CONFIG: &str = r#"
+[languages.python]
+timeout_duration = 3
+termination_grace_period = 1
+memory_limit = "64m"
+
+[output]
+max_capture_size = 4096
+"#;
+
+fn config() -> BotConfig {
+    toml::from_str(CONFIG).unwrap()
+}
+
+async fn run(executor: &dyn Executor, code: &str, stdin: &str) -> ExecutionResult {
+    let request = ExecutionRequest {
+        language: "python".into(),
+        files: vec![SourceFile {
+            name: "main.py".into(),
+            contents: code.into(),
+        }],
+        stdin: stdin.into(),
+    };
+
+    executor.execute(&request).await.unwrap()
+}
+
+async fn hello_world(executor: &dyn Executor) {
+    let result = run(executor, "print(f'hello {input()}')", "world\n").await;
+
+    assert_eq!(result.status, ExecutionStatus::Completed);
+    assert_eq!(result.exit_code, Some(0));
+    assert_eq!(result.stdout, "hello world\n");
+    assert_eq!(result.stderr, "");
+    assert!(!result.usage.wall_time.is_zero());
+}
+
+async fn timeout(executor: &dyn Executor) {
+    let code = "print('started', flush=True)\nwhile True: pass";
+    let result = run(executor, code, "").await;
+
+    assert_eq!(result.status, ExecutionStatus::TimedOut);
+    assert_eq!(result.exit_code, Some(124));
+    // Output from before the timeout is kept
+    assert_eq!(result.stdout, "started\n");
+}
+
+async fn out_of_memory(executor: &dyn Executor) {
+    let result = run(executor, "data = bytearray(512 * 1024 * 1024)", "").await;
+
+    assert_eq!(
+        result.status,
+        ExecutionStatus::OutOfMemory {
+            memory_limit: "64m".into()
+        }
+    );
+}
+
+async fn output_cap(executor: &dyn Executor) {
+    let result = run(executor, "while True: print('x' * 1000)", "").await;
+
+    assert_eq!(result.status, ExecutionStatus::OutputLimitExceeded);
+    assert!(result.stdout.len() + result.stderr.len() <= 4096);
+}
+
+async fn output_files(executor: &dyn Executor) {
+    let code = "import os\nos.makedirs('/out/plots', exist_ok=True)\n\
+                open('/out/result.txt', 'w').write('42')\n\
+                open('/out/plots/data.csv', 'w').write('x,y')";
+    let result = run(executor, code, "").await;
+
+    assert_eq!(result.status, ExecutionStatus::Completed);
+    let mut files = result
+        .files
+        .iter()
+        .map(|file| (file.name.as_str(), file.contents.as_slice()))
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            ("plots/data.csv", b"x,y".as_slice()),
+            ("result.txt", b"42".as_slice())
+        ]
+    );
+}
+
+async fn no_new_namespaces(executor: &dyn Executor) {
+    // Both ask for a new user namespace, which would let the program act as root in it
+    let code = r#"
+import ctypes, os, platform
+libc = ctypes.CDLL(None, use_errno=True)
+CLONE_NEWUSER, SIGCHLD = 0x10000000, 17
+SYS_clone = {'x86_64': 56, 'aarch64': 220}[platform.machine()]
+pid = libc.syscall(SYS_clone, CLONE_NEWUSER | SIGCHLD, 0, 0, 0, 0)
+if pid == 0:
+    os._exit(0)
+if pid > 0:
+    os.waitpid(pid, 0)
+print('clone', min(pid, 0))
+print('unshare', libc.unshare(CLONE_NEWUSER))
+"#;
+    let result = run(executor, code, "").await;
+
+    assert_eq!(result.status, ExecutionStatus::Completed);
+    assert_eq!(result.stdout, "clone -1\nunshare -1\n");
+}
+
+/// Runs every conformance test against the executor a backend's module builds.
+macro_rules! conformance_tests {
+    ($($backend:ident => $executor:expr;)*) => {$(
+        mod $backend {
+            use super::*;
+            use crate::executors::*;
+
+            #[tokio::test]
+            #[ignore = "needs the backend's sandbox"]
+            async fn hello_world() {
+                super::hello_world(&$executor).await
+            }
+
+            #[tokio::test]
+            #[ignore = "needs the backend's sandbox"]
+            async fn timeout() {
+                super::timeout(&$executor).await
+            }
+
+            #[tokio::test]
+            #[ignore = "needs the backend's sandbox"]
+            async fn out_of_memory() {
+                super::out_of_memory(&$executor).await
+            }
+
+            #[tokio::test]
+            #[ignore = "needs the backend's sandbox"]
+            async fn output_cap() {
+                super::output_cap(&$executor).await
+            }
+
+            #[tokio::test]
+            #[ignore = "needs the backend's sandbox"]
+            async fn output_files() {
+                super::output_files(&$executor).await
+            }
+
+            #[tokio::test]
+            #[ignore = "needs the backend's sandbox"]
+            async fn no_new_namespaces() {
+                super::no_new_namespaces(&$executor).await
+            }
+        }
+    )*};
+}
+
+conformance_tests! {
+    docker => DockerExecutor::from_config(&config(), ContainerEngine::Docker);
+    podman => DockerExecutor::from_config(&config(), ContainerEngine::Podman);
+    docker_api => DockerApiExecutor::from_config(&config());
+    nsjail => NsjailExecutor::from_config(&config());
+}
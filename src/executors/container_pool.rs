@@ -0,0 +1,194 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use super::docker::{ContainerEngine, max_age};
+use crate::{
+    config::{LanguageOverrides, PoolConfig},
+    runners::LANGUAGES,
+};
+
+/// Idle sandbox containers started ahead of time, so a run only waits for its script instead of
+/// a whole container starting. Each container is used for a single run and then thrown away.
+///
+/// Containers stop themselves once they have idled for the configured time, so they are only
+/// handed out while a run is sure to finish before then, and replaced once they aren't.
+pub struct ContainerPool {
+    config: PoolConfig,
+    engine: ContainerEngine,
+    languages: LanguageOverrides,
+    /// Names of each language's idle containers and when they were started, oldest first.
+    idle: Mutex<HashMap<&'static str, VecDeque<(String, Instant)>>>,
+    /// Wakes the refill loop whenever a container is taken.
+    taken: Notify,
+}
+
+impl ContainerPool {
+    pub fn new(config: PoolConfig, engine: ContainerEngine, languages: LanguageOverrides) -> Self {
+        Self {
+            config,
+            engine,
+            languages,
+            idle: Mutex::new(HashMap::new()),
+            taken: Notify::new(),
+        }
+    }
+
+    /// How long after starting a container for the language can still be given a run, or
+    /// `None` if a run could outlast even a fresh one.
+    fn usable_for(&self, language: &str) -> Option<Duration> {
+        self.config
+            .max_idle
+            .checked_sub(max_age(language, &self.languages))
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// Takes the oldest idle container for the language that is still running, if one is ready.
+    /// Containers that stopped while waiting, or could stop during the run, are thrown away.
+    pub async fn take(&self, language: &str) -> Option<String> {
+        let usable_for = self.usable_for(language)?;
+
+        loop {
+            let (container, started_at) =
+                self.idle.lock().unwrap().get_mut(language)?.pop_front()?;
+            self.taken.notify_one();
+
+            if started_at.elapsed() < usable_for && self.engine.is_running(&container).await {
+                return Some(container);
+            }
+
+            tracing::warn!("Discarding pool container that stopped or expired: {container}");
+            let _ = self.engine.remove_container(&container).await;
+        }
+    }
+
+    /// Keeps every language topped up with idle containers, replacing them as they are taken or
+    /// expire. Runs until the bot exits.
+    pub async fn refill(&self) {
+        for &language in LANGUAGES.keys() {
+            if self.usable_for(language).is_none() {
+                tracing::warn!(
+                    "Not pooling {language} containers, its runs can outlast pool.max_idle"
+                );
+            }
+        }
+
+        loop {
+            for container in self.remove_expired() {
+                let _ = self.engine.remove_container(&container).await;
+            }
+
+            let mut failed = false;
+
+            for (&language, config) in LANGUAGES.iter() {
+                if self.usable_for(language).is_none() {
+                    continue;
+                }
+
+                while self.idle_count(language) < self.config.size {
+                    let started_at = Instant::now();
+                    let security = self.languages.security_config(config.as_ref());
+                    match self
+                        .engine
+                        .start_idle_container(config.as_ref(), security, self.config.max_idle)
+                        .await
+                    {
+                        Ok(container_name) => {
+                            tracing::debug!(
+                                "Started pool container for {language} in {:?}",
+                                started_at.elapsed()
+                            );
+                            self.idle
+                                .lock()
+                                .unwrap()
+                                .entry(language)
+                                .or_default()
+                                .push_back((container_name, started_at));
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to start pool container for {language}: {e}");
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if failed {
+                tokio::time::sleep(Duration::from_secs(self.config.retry_interval)).await;
+                continue;
+            }
+
+            match self.next_expiry() {
+                Some(expiry) => {
+                    tokio::select! {
+                        _ = self.taken.notified() => {}
+                        _ = tokio::time::sleep_until(expiry.into()) => {}
+                    }
+                }
+                None => self.taken.notified().await,
+            }
+        }
+    }
+
+    /// Takes the containers that can no longer be given a run out of the pool.
+    fn remove_expired(&self) -> Vec<String> {
+        let mut idle = self.idle.lock().unwrap();
+        let mut expired = Vec::new();
+
+        for (language, containers) in idle.iter_mut() {
+            let usable_for = self.usable_for(language).unwrap_or_default();
+            while let Some((_, started_at)) = containers.front() {
+                if started_at.elapsed() < usable_for {
+                    break;
+                }
+                expired.extend(containers.pop_front().map(|(container, _)| container));
+            }
+        }
+
+        expired
+    }
+
+    /// When the next idle container stops being usable.
+    fn next_expiry(&self) -> Option<Instant> {
+        let idle = self.idle.lock().unwrap();
+
+        idle.iter()
+            .filter_map(|(language, containers)| {
+                let (_, started_at) = containers.front()?;
+                Some(*started_at + self.usable_for(language)?)
+            })
+            .min()
+    }
+
+    fn idle_count(&self, language: &str) -> usize {
+        self.idle
+            .lock()
+            .unwrap()
+            .get(language)
+            .map_or(0, VecDeque::len)
+    }
+}
@@ -0,0 +1,1075 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use tar::{Archive, EntryType};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::Command,
+};
+use uuid::Uuid;
+
+use super::{
+    ExecutionRequest, ExecutionResult, ExecutionStatus, Executor, OutputFile,
+    capture::{self, OutputCapture},
+    container_pool::ContainerPool,
+    script::{self, OUTPUT_DIR, WORKDIR},
+};
+use crate::{
+    config::{
+        BUILTIN_SECCOMP, BotConfig, InputConfig, LanguageOverrides, OutputConfig, SecurityConfig,
+    },
+    runners::{LANGUAGES, Language},
+};
+
+/// Identifies the containers started by this process, telling them apart from ones left behind
+/// by earlier runs of the bot.
+pub static INSTANCE_ID: LazyLock<String> = LazyLock::new(|| Uuid::new_v4().to_string());
+
+pub(super) const INSTANCE_LABEL: &str = "compiler-bot.instance";
+pub(super) const LANGUAGE_LABEL: &str = "compiler-bot.language";
+pub(super) const STARTED_LABEL: &str = "compiler-bot.started";
+/// Marks containers started ahead of time for the [`ContainerPool`].
+pub(super) const POOLED_LABEL: &str = "compiler-bot.pooled";
+
+/// How often containers that outlived their timeout are looked for and removed.
+const REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Extra age, on top of a language's timeout, allowed for container startup and cleanup before
+/// a container is considered orphaned.
+const REAP_SLACK: u64 = 60;
+
+/// Slack allowed on top of the output file size limit for tar headers and padding.
+pub(super) const TAR_OVERHEAD: usize = 64 * 1024;
+
+/// Seccomp profile of the hardened runners, built in so it is found wherever the bot runs from.
+pub(super) const SECCOMP_PROFILE: &str = include_str!("../../docker/seccomp.json");
+
+/// Where the built-in seccomp profile was written for the CLI, which only takes profiles as
+/// files. Every process of the user shares one copy, in a directory only they can write to.
+static SECCOMP_PROFILE_PATH: LazyLock<Result<String, String>> = LazyLock::new(|| {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok_or("Set XDG_RUNTIME_DIR or HOME to use the built-in seccomp profile")?
+        .join("compiler-bot");
+    let path = dir.join("seccomp.json");
+    write_if_changed(&dir, &path, SECCOMP_PROFILE.as_bytes())
+        .map_err(|e| format!("Failed to write seccomp profile to {}: {e}", path.display()))?;
+
+    Ok(path.to_string_lossy().into_owned())
+});
+
+/// Writes the file unless it already has these contents. It is replaced in one step, so other
+/// processes reading it never see it half written.
+fn write_if_changed(dir: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+    if fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir)?;
+    let staging = dir.join(format!(".{}", Uuid::new_v4()));
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&staging)
+        .and_then(|mut file| file.write_all(contents))
+        .and_then(|()| fs::rename(&staging, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&staging);
+    }
+
+    written
+}
+
+/// Main process of a pooled container, which idles until a run is started in it with
+/// `docker exec`, and stops the container once it has idled for `lifetime` seconds. Signals sent
+/// to the container only reach this process, so it passes SIGTERM on to the run while staying
+/// up itself so the run can report back.
+fn idle_script(lifetime: u64) -> String {
+    format!(
+        "trap 'kill -TERM -1' TERM; end=$((SECONDS + {lifetime})); \
+         while ((SECONDS < end)); do sleep $((end - SECONDS)) & wait $!; done"
+    )
+}
+
+/// Command line tool the sandbox containers are managed with. Podman's CLI mirrors Docker's
+/// closely enough for both to be driven the same way, apart from the differences handled here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerEngine {
+    Docker,
+    /// Podman, which can also run rootless. The container user then has to be inside the
+    /// subordinate ID range of the user running the bot.
+    Podman,
+}
+
+impl ContainerEngine {
+    fn command(self) -> Command {
+        Command::new(match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+        })
+    }
+
+    /// Go template that prints one of a container's labels in `ps` output.
+    fn label_template(self, label: &str) -> String {
+        match self {
+            Self::Docker => format!("{{{{.Label \"{label}\"}}}}"),
+            // Podman exposes the labels as a map
+            Self::Podman => format!("{{{{index .Labels \"{label}\"}}}}"),
+        }
+    }
+
+    /// Starts a container for the language that idles until a run is executed in it, for at
+    /// most `lifetime` seconds, returning its name.
+    pub async fn start_idle_container(
+        self,
+        config: &(dyn Language + Send + Sync),
+        security: SecurityConfig,
+        lifetime: u64,
+    ) -> Result<String, String> {
+        let container_name = format!("sandbox_{}_{}", config.name(), Uuid::new_v4());
+        let args = self.container_args(&container_name, config, security)?;
+
+        let run_result = tokio::time::timeout(
+            Duration::from_secs(60),
+            self.command()
+                .args(["run", "--detach"])
+                .args(args)
+                .arg("--label")
+                .arg(format!("{POOLED_LABEL}=true"))
+                .args([config.docker_image(), "bash", "-c", &idle_script(lifetime)])
+                .output(),
+        )
+        .await;
+
+        match run_result {
+            Ok(Ok(output)) if output.status.success() => Ok(container_name),
+            Ok(Ok(output)) => {
+                let _ = self.remove_container(&container_name).await;
+                Err(format!(
+                    "Failed to start container: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+            Ok(Err(e)) => Err(format!("Error executing {self:?} run: {e}")),
+            Err(_) => {
+                let _ = self.remove_container(&container_name).await;
+                Err("Timeout while starting container".into())
+            }
+        }
+    }
+
+    /// Force-removes sandbox containers from any instance of the bot that have outlived their
+    /// language's timeout, such as ones left running after a crash, returning how many were
+    /// removed. Pool containers, whose age says nothing about when their run started, are only
+    /// removed once they have stopped.
+    pub async fn reap_stale_containers(self, languages: &LanguageOverrides) -> usize {
+        let format = ["{{.Names}}".into(), "{{.State}}".into()]
+            .into_iter()
+            .chain(
+                [LANGUAGE_LABEL, STARTED_LABEL, INSTANCE_LABEL, POOLED_LABEL]
+                    .map(|label| self.label_template(label)),
+            )
+            .collect::<Vec<_>>()
+            .join("\t");
+        let list_result = tokio::time::timeout(
+            Duration::from_secs(10),
+            self.command()
+                .args(["ps", "--all", "--filter"])
+                .arg(format!("label={INSTANCE_LABEL}"))
+                .args(["--format", &format])
+                .output(),
+        )
+        .await;
+
+        let output = match list_result {
+            Ok(Ok(output)) if output.status.success() => output,
+            _ => {
+                tracing::error!("Failed to list sandbox containers");
+                return 0;
+            }
+        };
+
+        let now = unix_time();
+        let mut removed = 0;
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut fields = line.split('\t');
+            let (Some(name), Some(state)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let labels = [LANGUAGE_LABEL, STARTED_LABEL, INSTANCE_LABEL, POOLED_LABEL]
+                .into_iter()
+                .zip(fields)
+                .filter(|(_, value)| !value.is_empty())
+                .collect::<HashMap<_, _>>();
+
+            if is_stale(state, |label| labels.get(label).copied(), languages, now)
+                && self.remove_container(name).await.is_ok()
+            {
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            tracing::info!("Removed {removed} orphaned sandbox containers");
+        } else {
+            tracing::debug!("No orphaned sandbox containers found");
+        }
+
+        removed
+    }
+
+    /// Checks whether the container is still running, which a pooled one may not be if its idle
+    /// process died.
+    pub(super) async fn is_running(self, container_name: &str) -> bool {
+        let inspect_result = tokio::time::timeout(
+            Duration::from_secs(5),
+            self.command()
+                .args(["inspect", "--format", "{{.State.Running}}", container_name])
+                .output(),
+        )
+        .await;
+
+        match inspect_result {
+            Ok(Ok(output)) => String::from_utf8_lossy(&output.stdout).trim() == "true",
+            _ => {
+                tracing::error!("Failed to inspect container: {container_name}");
+                false
+            }
+        }
+    }
+
+    /// Checks whether the kernel killed anything in the stopped container for running out of
+    /// memory. This only covers the container's main process, so runs started with `docker exec`
+    /// are checked with [`Self::had_oom_kill`] instead.
+    async fn was_oom_killed(self, container_name: &str) -> bool {
+        let inspect_result = tokio::time::timeout(
+            Duration::from_secs(5),
+            self.command()
+                .args([
+                    "inspect",
+                    "--format",
+                    "{{.State.OOMKilled}}",
+                    container_name,
+                ])
+                .output(),
+        )
+        .await;
+
+        match inspect_result {
+            Ok(Ok(output)) => String::from_utf8_lossy(&output.stdout).trim() == "true",
+            _ => {
+                tracing::error!("Failed to inspect container: {container_name}");
+                false
+            }
+        }
+    }
+
+    /// Checks the running container's cgroup for processes killed for running out of memory.
+    /// Pooled containers only ever run a single job, so any kill there was during that job.
+    async fn had_oom_kill(self, container_name: &str) -> bool {
+        let events_result = tokio::time::timeout(
+            Duration::from_secs(5),
+            self.command()
+                .args([
+                    "exec",
+                    container_name,
+                    "cat",
+                    "/sys/fs/cgroup/memory.events",
+                ])
+                .output(),
+        )
+        .await;
+
+        match events_result {
+            Ok(Ok(output)) if output.status.success() => {
+                oom_kill_count(&String::from_utf8_lossy(&output.stdout)) > 0
+            }
+            _ => {
+                tracing::error!("Failed to read memory events of container: {container_name}");
+                false
+            }
+        }
+    }
+
+    pub(super) async fn remove_container(self, container_name: &str) -> Result<(), String> {
+        let mut command = self.command();
+        command.args(["rm", "--force", "--volumes"]);
+        if self == Self::Podman {
+            // Podman stops running containers gracefully before removing them unless told not to
+            command.args(["--time", "0"]);
+        }
+
+        let remove_result = tokio::time::timeout(
+            Duration::from_secs(10),
+            command.arg(container_name).output(),
+        )
+        .await;
+
+        match remove_result {
+            Ok(Ok(output)) if output.status.success() => Ok(()),
+            Ok(Ok(output)) => {
+                let error = String::from_utf8_lossy(&output.stderr);
+                tracing::error!("Failed to remove container {container_name}: {error}");
+                Err(format!("Failed to remove container: {error}"))
+            }
+            Ok(Err(e)) => {
+                tracing::error!("Error executing {self:?} rm: {e}");
+                Err(format!("Error executing {self:?} rm: {e}"))
+            }
+            Err(_) => {
+                tracing::error!("Timeout while removing container: {container_name}");
+                Err("Timeout while removing container".into())
+            }
+        }
+    }
+
+    /// Builds the `docker run` options for a sandbox container, deriving every sandboxing flag
+    /// from the language's security configuration. The image and command go after these.
+    fn container_args(
+        self,
+        container_name: &str,
+        config: &(dyn Language + Send + Sync),
+        security: SecurityConfig,
+    ) -> Result<Vec<String>, String> {
+        let mut args = vec!["--name".into(), container_name.into()];
+
+        // Labels for finding the container again if it gets orphaned
+        args.extend([
+            "--label".into(),
+            format!("{INSTANCE_LABEL}={}", *INSTANCE_ID),
+            "--label".into(),
+            format!("{LANGUAGE_LABEL}={}", config.name()),
+            "--label".into(),
+            format!("{STARTED_LABEL}={}", unix_time()),
+        ]);
+
+        // Network access
+        if security.disable_network {
+            args.extend(["--network".into(), "none".into()]);
+        } else if let Some(network) = security.network {
+            args.extend(["--network".into(), network]);
+        }
+
+        // Resource limits
+        args.extend([
+            "--cpus".into(),
+            security.cpu_limit,
+            "--memory".into(),
+            security.memory_limit,
+            "--pids-limit".into(),
+            security.pids_limit.to_string(),
+            "--ulimit".into(),
+            format!("nofile={}", security.file_descriptor_limit),
+        ]);
+
+        // Privileges
+        if let Some(runtime) = security.runtime {
+            args.extend(["--runtime".into(), runtime]);
+        }
+        if security.no_new_privileges {
+            // Podman only accepts the bare form
+            let option = match self {
+                Self::Docker => "no-new-privileges:true",
+                Self::Podman => "no-new-privileges",
+            };
+            args.extend(["--security-opt".into(), option.into()]);
+        }
+        if let Some(profile) = security.seccomp_profile {
+            let profile = match profile.as_str() {
+                BUILTIN_SECCOMP => SECCOMP_PROFILE_PATH.clone()?,
+                _ => profile,
+            };
+            args.extend(["--security-opt".into(), format!("seccomp={profile}")]);
+        }
+        if let Some(user) = security.user {
+            args.extend(["--user".into(), user]);
+        }
+        for capability in security.cap_drop {
+            args.extend(["--cap-drop".into(), capability]);
+        }
+        for capability in security.cap_add {
+            args.extend(["--cap-add".into(), capability]);
+        }
+
+        // Filesystem
+        if security.read_only_rootfs {
+            args.push("--read-only".into());
+            for path in [WORKDIR, "/tmp"] {
+                args.extend([
+                    "--tmpfs".into(),
+                    format!("{path}:rw,exec,size={}", security.tmpfs_size),
+                ]);
+            }
+            // Output files are copied out after the container stops, which a tmpfs won't survive
+            args.extend(["--volume".into(), OUTPUT_DIR.into()]);
+            // Give tools that write to the home directory somewhere writable
+            args.extend(["--env".into(), "HOME=/tmp".into()]);
+        }
+
+        args.extend(["--workdir".into(), WORKDIR.into()]);
+        for (key, value) in config.environment() {
+            args.extend(["--env".into(), format!("{key}={value}")]);
+        }
+
+        Ok(args)
+    }
+
+    async fn kill_container(self, container_name: &str, signal: &str) -> Result<(), String> {
+        tracing::warn!("Attempting to send SIG{signal} to container: {container_name}");
+
+        let kill_result = tokio::time::timeout(
+            Duration::from_secs(5),
+            self.command()
+                .args(["kill", "--signal", signal, container_name])
+                .output(),
+        )
+        .await;
+
+        match kill_result {
+            Ok(Ok(output)) => {
+                let error = String::from_utf8_lossy(&output.stderr);
+                if output.status.success() {
+                    tracing::info!("Successfully killed container: {container_name}");
+                    Ok(())
+                } else if error.contains("not running") || error.contains("only kill running") {
+                    // The container exited on its own in the meantime, which Podman words
+                    // differently from Docker
+                    tracing::debug!("Container already stopped: {container_name}");
+                    Ok(())
+                } else {
+                    tracing::error!("Failed to kill container {container_name}: {error}");
+                    Err(format!("Failed to kill container: {error}"))
+                }
+            }
+            Ok(Err(e)) => {
+                tracing::error!("Error executing {self:?} kill: {e}");
+                Err(format!("Error executing {self:?} kill: {e}"))
+            }
+            Err(_) => {
+                tracing::error!("Timeout while killing container: {container_name}");
+                Err("Timeout while killing container".into())
+            }
+        }
+    }
+}
+
+/// Runs each job in a fresh container, or a warm one from the pool, managed through the Docker or
+/// Podman CLI.
+pub struct DockerExecutor {
+    pub engine: ContainerEngine,
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub languages: LanguageOverrides,
+    /// Idle containers to run in instead of starting a new one, when any are ready.
+    pub pool: Option<Arc<ContainerPool>>,
+}
+
+impl DockerExecutor {
+    pub fn new() -> Self {
+        Self {
+            engine: ContainerEngine::Docker,
+            input: InputConfig::default(),
+            output: OutputConfig::default(),
+            languages: LanguageOverrides::default(),
+            pool: None,
+        }
+    }
+
+    /// Creates the executor with the configured limits, starting its container pool if one is
+    /// configured and the periodic cleanup of orphaned containers.
+    pub fn from_config(config: &BotConfig, engine: ContainerEngine) -> Self {
+        spawn_reaper({
+            let languages = config.languages.clone();
+            move || {
+                let languages = languages.clone();
+                async move { engine.reap_stale_containers(&languages).await }
+            }
+        });
+
+        let pool = (config.pool.size > 0).then(|| {
+            let pool = Arc::new(ContainerPool::new(
+                config.pool.clone(),
+                engine,
+                config.languages.clone(),
+            ));
+            tokio::spawn({
+                let pool = pool.clone();
+                async move { pool.refill().await }
+            });
+            pool
+        });
+
+        Self {
+            pool,
+            ..Self::with_config(config, engine)
+        }
+    }
+
+    /// Creates the executor with the configured limits and no pool, starting no background tasks.
+    pub fn with_config(config: &BotConfig, engine: ContainerEngine) -> Self {
+        Self {
+            engine,
+            input: config.input.clone(),
+            output: config.output.clone(),
+            languages: config.languages.clone(),
+            pool: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for DockerExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        let ExecutionRequest {
+            language,
+            files,
+            stdin,
+        } = request;
+        let language = language.as_str();
+
+        // Validate input
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+        request.validate(&self.input)?;
+
+        let config = LANGUAGES
+            .get(language)
+            .ok_or_else(|| format!("Unsupported language: {language}"))?;
+
+        let (files, command) = script::prepare_files(files, config.as_ref())?;
+        let script = script::run_script(&command);
+        let security = self.languages.security_config(config.as_ref());
+
+        // Run in a warm container from the pool if one is ready, or start a new one
+        let pooled = match &self.pool {
+            Some(pool) => pool.take(language).await,
+            None => None,
+        };
+        let warm = pooled.is_some();
+        let mut docker_cmd = self.engine.command();
+        let container_name = match pooled {
+            Some(container_name) => {
+                docker_cmd.args(["exec", "-i", &container_name, "bash", "-c", &script]);
+                container_name
+            }
+            None => {
+                let container_name = format!("sandbox_{}_{}", language, Uuid::new_v4());
+                let args = self.engine.container_args(
+                    &container_name,
+                    config.as_ref(),
+                    security.clone(),
+                )?;
+                docker_cmd.arg("run").args(args).args([
+                    "-i",
+                    config.docker_image(),
+                    "bash",
+                    "-c",
+                    &script,
+                ]);
+                container_name
+            }
+        };
+
+        // Configure stdio
+        docker_cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        tracing::info!(
+            "Executing Docker command for language: {language} ({})",
+            if warm { "warm" } else { "cold" }
+        );
+
+        // Start the process
+        let marker = Uuid::new_v4().to_string();
+        let started_at = Instant::now();
+        let mut child = docker_cmd
+            .spawn()
+            .map_err(|e| format!("Failed to spawn Docker process: {e}"))?;
+
+        // Write code to stdin
+        if let Some(mut child_stdin) = child.stdin.take() {
+            let payload = script::payload(&marker, config.as_ref(), &files);
+            if let Err(e) = child_stdin.write_all(&payload).await {
+                tracing::error!("Failed to write to stdin: {e}");
+                // Try to remove the container
+                let _ = self.engine.remove_container(&container_name).await;
+                return Err(format!("Failed to write code to container: {e}"));
+            }
+
+            // Feed the program's input separately so a program that writes before reading
+            // cannot deadlock against us, then close stdin to signal EOF
+            let stdin = stdin.to_owned();
+            tokio::spawn(async move {
+                if let Err(e) = child_stdin.write_all(stdin.as_bytes()).await {
+                    tracing::debug!("Program did not consume all of its input: {e}");
+                }
+            });
+        }
+
+        let (Some(mut stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take())
+        else {
+            let _ = self.engine.remove_container(&container_name).await;
+            return Err("Failed to capture the container's output".into());
+        };
+
+        // Wait for execution with timeout, killing the container early if it floods its output
+        let mut capture = OutputCapture::new(self.output.max_capture_size);
+        let signal = |signal| {
+            let container_name = &container_name;
+            async move {
+                let _ = self.engine.kill_container(container_name, signal).await;
+            }
+        };
+        let outcome = capture::supervise(
+            &mut child,
+            &mut capture,
+            &mut stdout,
+            &mut stderr,
+            &security,
+            signal,
+        )
+        .await;
+
+        let mut result = match outcome {
+            Ok(outcome) => {
+                // The container's own OOM flag only covers its idle process when the run was
+                // started with exec, so that is checked separately below
+                let oom_killed =
+                    async { !warm && self.engine.was_oom_killed(&container_name).await };
+                let mut result = outcome
+                    .into_result(
+                        capture,
+                        &marker,
+                        started_at.elapsed(),
+                        oom_killed,
+                        &security,
+                    )
+                    .await;
+                // A job the OOM killer got exits with 137 like any other SIGKILL
+                if warm
+                    && result.status == ExecutionStatus::Signaled(9)
+                    && self.engine.had_oom_kill(&container_name).await
+                {
+                    result.status = ExecutionStatus::OutOfMemory {
+                        memory_limit: security.memory_limit.clone(),
+                    };
+                }
+                // Everything but the run itself, which is what the pool saves on
+                tracing::info!(
+                    "Container overhead for {language} ({}): {:?}",
+                    if warm { "warm" } else { "cold" },
+                    started_at.elapsed().saturating_sub(result.usage.wall_time)
+                );
+                Ok(result)
+            }
+            Err(e) => {
+                let _ = self.engine.kill_container(&container_name, "KILL").await;
+                Err(format!("Process execution failed: {e}"))
+            }
+        };
+
+        // The container is kept after it exits so its output files can be copied out
+        if let Ok(result) = &mut result {
+            result.files = self.collect_output_files(&container_name).await;
+        }
+        let _ = self.engine.remove_container(&container_name).await;
+
+        result
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        LANGUAGES.keys().copied().collect()
+    }
+}
+
+impl DockerExecutor {
+    /// Copies the files the program wrote to the output directory out of the stopped container,
+    /// keeping as many as fit within the configured count and size limits.
+    async fn collect_output_files(&self, container_name: &str) -> Vec<OutputFile> {
+        let child = self
+            .engine
+            .command()
+            .args(["cp", &format!("{container_name}:{OUTPUT_DIR}"), "-"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+
+        let Ok(mut child) = child else {
+            tracing::error!("Failed to spawn docker cp for container: {container_name}");
+            return Vec::new();
+        };
+
+        // Read no more of the archive than the limits could ever need
+        let mut archive = Vec::new();
+        let limit = self.output.max_files_size + TAR_OVERHEAD;
+        if let Some(stdout) = child.stdout.take() {
+            let read = tokio::time::timeout(
+                Duration::from_secs(10),
+                stdout.take(limit as u64).read_to_end(&mut archive),
+            )
+            .await;
+
+            if !matches!(read, Ok(Ok(_))) {
+                tracing::error!("Failed to read output files from container: {container_name}");
+                return Vec::new();
+            }
+        }
+
+        unpack_output_files(&archive, &self.output)
+    }
+}
+
+/// Reads the files out of a tar archive of the output directory, keeping as many as fit within
+/// the configured count and size limits.
+pub(super) fn unpack_output_files(archive: &[u8], output: &OutputConfig) -> Vec<OutputFile> {
+    let mut files = Vec::new();
+    let mut total_size = 0;
+
+    let mut archive = Archive::new(archive);
+    let Ok(entries) = archive.entries() else {
+        return files;
+    };
+
+    // Stop at the first unreadable entry, which is where an oversized archive was cut off
+    for mut entry in entries.map_while(Result::ok) {
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+
+        // Entries are prefixed with the name of the copied directory
+        let Some(name) = entry.path().ok().and_then(|path| {
+            let relative = path.components().skip(1).collect::<PathBuf>();
+            relative.to_str().map(String::from)
+        }) else {
+            continue;
+        };
+
+        let size = entry.size() as usize;
+        if files.len() >= output.max_files || total_size + size > output.max_files_size {
+            tracing::warn!("Skipping output file over the limits: {name}");
+            continue;
+        }
+
+        let mut contents = Vec::with_capacity(size);
+        if entry.read_to_end(&mut contents).is_err() {
+            break;
+        }
+
+        total_size += size;
+        files.push(OutputFile { name, contents });
+    }
+
+    files
+}
+
+/// Reads how many processes the OOM killer has killed from a cgroup's `memory.events`.
+fn oom_kill_count(memory_events: &str) -> u64 {
+    memory_events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+/// Age in seconds after which a container running the language is considered orphaned.
+/// Cleans up sandbox containers left behind by crashes with `reap`, on startup and periodically
+/// after.
+pub(super) fn spawn_reaper<F>(reap: impl Fn() -> F + Send + 'static)
+where
+    F: Future<Output = usize> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REAP_INTERVAL);
+        loop {
+            interval.tick().await;
+            reap().await;
+        }
+    });
+}
+
+/// Whether a sandbox container from any instance of the bot is left over and should be removed,
+/// from its state, such as `running`, and its labels. Pool containers are only removed once they
+/// have stopped, since they are created long before the run they are given.
+pub(super) fn is_stale<'a>(
+    state: &str,
+    label: impl Fn(&'static str) -> Option<&'a str>,
+    languages: &LanguageOverrides,
+    now: u64,
+) -> bool {
+    let (Some(language), Some(Ok(started))) = (
+        label(LANGUAGE_LABEL),
+        label(STARTED_LABEL).map(str::parse::<u64>),
+    ) else {
+        return false;
+    };
+
+    match label(POOLED_LABEL) == Some("true") {
+        true => state != "running",
+        false => now.saturating_sub(started) > max_age(language, languages),
+    }
+}
+
+pub(super) fn max_age(language: &str, languages: &LanguageOverrides) -> u64 {
+    let security = LANGUAGES
+        .get(language)
+        .map(|config| languages.security_config(config.as_ref()))
+        .unwrap_or_default();
+
+    security.timeout_duration + security.termination_grace_period + REAP_SLACK
+}
+
+pub(super) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+impl Default for DockerExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container_args(
+        engine: ContainerEngine,
+        config: &(dyn Language + Send + Sync),
+    ) -> Vec<String> {
+        engine
+            .container_args("sandbox_test", config, config.security_config())
+            .unwrap()
+    }
+
+    /// Whether the flag is immediately followed by the value somewhere in the arguments.
+    fn has_option(args: &[String], flag: &str, value: &str) -> bool {
+        args.windows(2)
+            .any(|pair| pair[0] == flag && pair[1] == value)
+    }
+
+    #[test]
+    fn container_args_apply_each_languages_security_config() {
+        for (name, config) in LANGUAGES.iter() {
+            let security = config.security_config();
+            let args = container_args(ContainerEngine::Docker, config.as_ref());
+
+            assert!(has_option(&args, "--network", "none"), "{name}");
+            assert!(has_option(&args, "--cpus", &security.cpu_limit), "{name}");
+            assert!(
+                has_option(&args, "--memory", &security.memory_limit),
+                "{name}"
+            );
+            assert!(
+                has_option(&args, "--pids-limit", &security.pids_limit.to_string()),
+                "{name}"
+            );
+            assert!(
+                has_option(
+                    &args,
+                    "--ulimit",
+                    &format!("nofile={}", security.file_descriptor_limit)
+                ),
+                "{name}"
+            );
+            assert!(
+                has_option(&args, "--security-opt", "no-new-privileges:true"),
+                "{name}"
+            );
+            assert!(
+                has_option(&args, "--label", &format!("{LANGUAGE_LABEL}={name}")),
+                "{name}"
+            );
+            assert!(has_option(&args, "--workdir", WORKDIR), "{name}");
+        }
+    }
+
+    #[test]
+    fn container_args_harden_each_language() {
+        for (name, config) in LANGUAGES.iter() {
+            let security = config.security_config();
+            let args = container_args(ContainerEngine::Docker, config.as_ref());
+
+            assert!(args.contains(&"--read-only".into()), "{name}");
+            for path in [WORKDIR, "/tmp"] {
+                let mount = format!("{path}:rw,exec,size={}", security.tmpfs_size);
+                assert!(has_option(&args, "--tmpfs", &mount), "{name}");
+            }
+            assert!(has_option(&args, "--volume", OUTPUT_DIR), "{name}");
+            assert!(has_option(&args, "--user", "10001:10001"), "{name}");
+            assert!(has_option(&args, "--cap-drop", "ALL"), "{name}");
+            let profile = args
+                .iter()
+                .find_map(|arg| arg.strip_prefix("seccomp="))
+                .unwrap();
+            assert_eq!(
+                fs::read_to_string(profile).unwrap(),
+                SECCOMP_PROFILE,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn container_args_set_each_languages_environment() {
+        for (name, config) in LANGUAGES.iter() {
+            let args = container_args(ContainerEngine::Docker, config.as_ref());
+
+            for (key, value) in config.environment() {
+                assert!(
+                    has_option(&args, "--env", &format!("{key}={value}")),
+                    "{name}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn container_args_follow_podmans_syntax() {
+        let config = LANGUAGES["python"].as_ref();
+        let args = container_args(ContainerEngine::Podman, config);
+
+        assert!(has_option(&args, "--security-opt", "no-new-privileges"));
+        assert!(!args.contains(&"no-new-privileges:true".into()));
+    }
+
+    #[test]
+    fn container_args_apply_configured_overrides() {
+        let config = LANGUAGES["python"].as_ref();
+        let languages = toml::from_str::<LanguageOverrides>(
+            r#"
+            [python]
+            runtime = "runsc"
+            memory_limit = "512m"
+            seccomp_profile = "/etc/compiler-bot/seccomp.json"
+            "#,
+        )
+        .unwrap();
+
+        let security = languages.security_config(config);
+        let args = ContainerEngine::Docker
+            .container_args("sandbox_test", config, security)
+            .unwrap();
+
+        assert!(has_option(&args, "--runtime", "runsc"));
+        assert!(has_option(&args, "--memory", "512m"));
+        assert!(has_option(
+            &args,
+            "--security-opt",
+            "seccomp=/etc/compiler-bot/seccomp.json"
+        ));
+        // Everything else is left as the runner has it
+        assert!(has_option(&args, "--user", "10001:10001"));
+        assert_eq!(
+            max_age("python", &languages),
+            max_age("python", &Default::default())
+        );
+    }
+
+    #[test]
+    fn oom_kill_count_reads_memory_events() {
+        let events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\noom_group_kill 0\n";
+        assert_eq!(oom_kill_count(events), 1);
+        assert_eq!(oom_kill_count("low 0\nmax 0\noom 0\noom_kill 0\n"), 0);
+        assert_eq!(oom_kill_count(""), 0);
+    }
+
+    #[test]
+    fn write_if_changed_replaces_only_stale_files() {
+        let dir = env::temp_dir().join(format!("compiler-bot-test-{}", Uuid::new_v4()));
+        let path = dir.join("seccomp.json");
+
+        write_if_changed(&dir, &path, b"old").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+        write_if_changed(&dir, &path, b"old").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        write_if_changed(&dir, &path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        // Nothing is left behind besides the file itself
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_script_stops_after_its_lifetime() {
+        let started_at = Instant::now();
+        let status = Command::new("bash")
+            .args(["-c", &idle_script(1)])
+            .status()
+            .await
+            .unwrap();
+
+        assert!(status.success());
+        assert!(started_at.elapsed() >= Duration::from_secs(1));
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn is_stale_ages_out_only_runs() {
+        let languages = LanguageOverrides::default();
+        let max_age = max_age("python", &languages);
+        let labels = |pooled| {
+            HashMap::from([
+                (LANGUAGE_LABEL, "python"),
+                (STARTED_LABEL, "1000"),
+                (INSTANCE_LABEL, "another-instance"),
+                (POOLED_LABEL, pooled),
+            ])
+        };
+        let stale = |state, labels: &HashMap<_, _>, now| {
+            is_stale(state, |label| labels.get(label).copied(), &languages, now)
+        };
+
+        // Runs are left over once they outlive their timeout
+        let run = labels("false");
+        assert!(!stale("running", &run, 1000 + max_age));
+        assert!(stale("running", &run, 1000 + max_age + 1));
+
+        // Pool containers from any instance are kept however old, until they stop
+        let pooled = labels("true");
+        assert!(!stale("running", &pooled, 1000 + max_age * 100));
+        assert!(stale("exited", &pooled, 1000));
+
+        // Containers without the labels weren't started by the bot
+        assert!(!stale("exited", &HashMap::new(), 1000 + max_age * 100));
+    }
+}
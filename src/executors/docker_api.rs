@@ -0,0 +1,447 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fs, os::unix::process::ExitStatusExt, process::ExitStatus, time::Instant};
+
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use tokio::io::{self, AsyncWriteExt};
+use uuid::Uuid;
+
+use super::{
+    ExecutionRequest, ExecutionResult, Executor, OutputFile,
+    capture::{self, OutputCapture, Sandbox},
+    docker::{
+        INSTANCE_ID, INSTANCE_LABEL, LANGUAGE_LABEL, SECCOMP_PROFILE, STARTED_LABEL, TAR_OVERHEAD,
+        is_stale, spawn_reaper, unix_time, unpack_output_files,
+    },
+    docker_client::{self, Attachment, DockerClient, DockerError},
+    parse_size,
+    script::{self, OUTPUT_DIR, WORKDIR},
+};
+use crate::{
+    config::{
+        BUILTIN_SECCOMP, BotConfig, InputConfig, LanguageOverrides, OutputConfig, SecurityConfig,
+    },
+    runners::{LANGUAGES, Language},
+};
+
+/// Buffer between the demultiplexed container output and the capture.
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// Runs each job in a fresh container managed through the Docker Engine API on the daemon's Unix
+/// socket, rather than by spawning the CLI. Failures come back as structured errors and the exit
+/// code and OOM kills are read straight from the daemon.
+pub struct DockerApiExecutor {
+    pub client: DockerClient,
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub languages: LanguageOverrides,
+}
+
+impl DockerApiExecutor {
+    /// Creates the executor with the configured limits, starting the periodic cleanup of
+    /// orphaned containers.
+    pub fn from_config(config: &BotConfig) -> Self {
+        if config.pool.size > 0 {
+            tracing::warn!("The container pool isn't supported by the docker_api backend");
+        }
+
+        let executor = Self::with_config(config);
+        spawn_reaper({
+            let (client, languages) = (executor.client.clone(), config.languages.clone());
+            move || {
+                let (client, languages) = (client.clone(), languages.clone());
+                async move { reap_stale_containers(&client, &languages).await }
+            }
+        });
+
+        executor
+    }
+
+    /// Creates the executor with the configured limits, starting no background tasks.
+    pub fn with_config(config: &BotConfig) -> Self {
+        Self {
+            client: DockerClient::new(&config.docker_api.socket),
+            input: config.input.clone(),
+            output: config.output.clone(),
+            languages: config.languages.clone(),
+        }
+    }
+
+    /// Creates the container, pulling its image first if the daemon doesn't have it yet.
+    async fn create_container(
+        &self,
+        name: &str,
+        config: &(dyn Language + Send + Sync),
+        security: &SecurityConfig,
+        script: &str,
+    ) -> Result<String, String> {
+        let container_config = container_config(config, security, script)?;
+
+        let created = match self.client.create_container(name, &container_config).await {
+            Err(e) if e.status() == Some(404) => {
+                tracing::info!("Pulling image {}", config.docker_image());
+                self.client
+                    .pull_image(config.docker_image())
+                    .await
+                    .map_err(|e| format!("Failed to pull image: {e}"))?;
+                self.client.create_container(name, &container_config).await
+            }
+            created => created,
+        };
+
+        created.map_err(|e| format!("Failed to create container: {e}"))
+    }
+
+    /// Runs the created container to completion, feeding it the files and input.
+    async fn run(
+        &self,
+        id: &str,
+        config: &(dyn Language + Send + Sync),
+        files: &[super::SourceFile],
+        stdin: &str,
+        security: &SecurityConfig,
+    ) -> Result<ExecutionResult, String> {
+        let Attachment { output, mut input } = self
+            .client
+            .attach(id)
+            .await
+            .map_err(|e| format!("Failed to attach to container: {e}"))?;
+        self.client
+            .start(id)
+            .await
+            .map_err(|e| format!("Failed to start container: {e}"))?;
+
+        let marker = Uuid::new_v4().to_string();
+        let started_at = Instant::now();
+
+        // Write code to stdin
+        let payload = script::payload(&marker, config, files);
+        if let Err(e) = input.write_all(&payload).await {
+            tracing::error!("Failed to write to stdin: {e}");
+            return Err(format!("Failed to write code to container: {e}"));
+        }
+
+        // Feed the program's input separately so a program that writes before reading cannot
+        // deadlock against us, then close stdin to signal EOF
+        let stdin = stdin.to_owned();
+        tokio::spawn(async move {
+            if let Err(e) = input.write_all(stdin.as_bytes()).await {
+                tracing::debug!("Program did not consume all of its input: {e}");
+            }
+            let _ = input.shutdown().await;
+        });
+
+        let (stdout_writer, mut stdout) = io::duplex(PIPE_SIZE);
+        let (stderr_writer, mut stderr) = io::duplex(PIPE_SIZE);
+        tokio::spawn(async move {
+            if let Err(e) = docker_client::demultiplex(output, stdout_writer, stderr_writer).await {
+                tracing::debug!("Stopped reading container output: {e}");
+            }
+        });
+
+        // Wait for execution with timeout, killing the container early if it floods its output
+        let mut capture = OutputCapture::new(self.output.max_capture_size);
+        let mut container = Container {
+            client: &self.client,
+            id,
+        };
+        let signal = |signal| async move {
+            let _ = self.kill_container(id, signal).await;
+        };
+        let outcome = capture::supervise(
+            &mut container,
+            &mut capture,
+            &mut stdout,
+            &mut stderr,
+            security,
+            signal,
+        )
+        .await;
+
+        match outcome {
+            Ok(outcome) => Ok(outcome
+                .into_result(
+                    capture,
+                    &marker,
+                    started_at.elapsed(),
+                    self.was_oom_killed(id),
+                    security,
+                )
+                .await),
+            Err(e) => {
+                let _ = self.kill_container(id, "KILL").await;
+                Err(format!("Container execution failed: {e}"))
+            }
+        }
+    }
+
+    async fn kill_container(&self, id: &str, signal: &str) -> Result<(), DockerError> {
+        tracing::warn!("Attempting to send SIG{signal} to container: {id}");
+
+        match self.client.kill(id, signal).await {
+            Ok(()) => {
+                tracing::info!("Successfully killed container: {id}");
+                Ok(())
+            }
+            // The container exited on its own in the meantime
+            Err(e) if e.status() == Some(409) => {
+                tracing::debug!("Container already stopped: {id}");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::error!("Failed to kill container {id}: {e}");
+                Err(e)
+            }
+        }
+    }
+
+    async fn was_oom_killed(&self, id: &str) -> bool {
+        match self.client.inspect(id).await {
+            Ok(state) => state.oom_killed,
+            Err(e) => {
+                tracing::error!("Failed to inspect container {id}: {e}");
+                false
+            }
+        }
+    }
+
+    /// Downloads the files the program wrote to the output directory from the stopped
+    /// container, keeping as many as fit within the configured count and size limits.
+    async fn collect_output_files(&self, id: &str) -> Vec<OutputFile> {
+        // Read no more of the archive than the limits could ever need
+        let limit = self.output.max_files_size + TAR_OVERHEAD;
+        match self.client.archive(id, OUTPUT_DIR, limit).await {
+            Ok(archive) => unpack_output_files(&archive, &self.output),
+            Err(e) => {
+                tracing::error!("Failed to read output files from container {id}: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn remove_container(&self, id: &str) {
+        if let Err(e) = self.client.remove(id).await {
+            tracing::error!("Failed to remove container {id}: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for DockerApiExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        let ExecutionRequest {
+            language,
+            files,
+            stdin,
+        } = request;
+        let language = language.as_str();
+
+        // Validate input
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+        request.validate(&self.input)?;
+
+        let config = LANGUAGES
+            .get(language)
+            .ok_or_else(|| format!("Unsupported language: {language}"))?;
+
+        let (files, command) = script::prepare_files(files, config.as_ref())?;
+        let script = script::run_script(&command);
+        let security = self.languages.security_config(config.as_ref());
+
+        tracing::info!("Executing container through the Docker API for language: {language}");
+
+        let name = format!("sandbox_{}_{}", language, Uuid::new_v4());
+        let id = self
+            .create_container(&name, config.as_ref(), &security, &script)
+            .await?;
+
+        let mut result = self
+            .run(&id, config.as_ref(), &files, stdin, &security)
+            .await;
+
+        // The container is kept after it exits so its output files can be copied out
+        if let Ok(result) = &mut result {
+            result.files = self.collect_output_files(&id).await;
+        }
+        self.remove_container(&id).await;
+
+        result
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        LANGUAGES.keys().copied().collect()
+    }
+}
+
+/// A started container, waited on through the API.
+struct Container<'a> {
+    client: &'a DockerClient,
+    id: &'a str,
+}
+
+impl Sandbox for Container<'_> {
+    fn wait(&mut self) -> impl Future<Output = io::Result<ExitStatus>> + Send {
+        let (client, id) = (self.client, self.id);
+        async move {
+            let code = client.wait(id).await.map_err(io::Error::other)?;
+            // Encoded like a wait status so it reads back as the same exit code
+            Ok(ExitStatus::from_raw(((code & 0xff) as i32) << 8))
+        }
+    }
+}
+
+/// Builds the container configuration for a sandbox, deriving every sandboxing option from the
+/// language's security configuration, to the same effect as the CLI's `docker run` flags.
+fn container_config(
+    config: &(dyn Language + Send + Sync),
+    security: &SecurityConfig,
+    script: &str,
+) -> Result<Value, String> {
+    let memory = parse_size(&security.memory_limit)
+        .ok_or_else(|| format!("Invalid memory limit: {}", security.memory_limit))?;
+    let cpus = security
+        .cpu_limit
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid CPU limit: {}", security.cpu_limit))?;
+    let (soft, hard) = match security.file_descriptor_limit.split_once(':') {
+        Some((soft, hard)) => (soft, hard),
+        None => (
+            security.file_descriptor_limit.as_str(),
+            security.file_descriptor_limit.as_str(),
+        ),
+    };
+    let (Ok(soft), Ok(hard)) = (soft.parse::<u64>(), hard.parse::<u64>()) else {
+        return Err(format!(
+            "Invalid file descriptor limit: {}",
+            security.file_descriptor_limit
+        ));
+    };
+
+    let mut security_opt = Vec::new();
+    if security.no_new_privileges {
+        security_opt.push("no-new-privileges:true".to_string());
+    }
+    if let Some(profile) = &security.seccomp_profile {
+        // The CLI sends the profile itself, since the daemon may not be able to read the file
+        let profile = match profile.as_str() {
+            "unconfined" => profile.clone(),
+            BUILTIN_SECCOMP => SECCOMP_PROFILE.into(),
+            path => fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read seccomp profile {path}: {e}"))?,
+        };
+        security_opt.push(format!("seccomp={profile}"));
+    }
+
+    let mut host_config = json!({
+        "NanoCpus": (cpus * 1e9) as u64,
+        "Memory": memory,
+        "PidsLimit": security.pids_limit,
+        "Ulimits": [{ "Name": "nofile", "Soft": soft, "Hard": hard }],
+        "SecurityOpt": security_opt,
+        "CapAdd": security.cap_add,
+        "CapDrop": security.cap_drop,
+        "ReadonlyRootfs": security.read_only_rootfs,
+    });
+
+    // Network access
+    if security.disable_network {
+        host_config["NetworkMode"] = "none".into();
+    } else if let Some(network) = &security.network {
+        host_config["NetworkMode"] = network.as_str().into();
+    }
+    if let Some(runtime) = &security.runtime {
+        host_config["Runtime"] = runtime.as_str().into();
+    }
+
+    let mut env = config
+        .environment()
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+    let mut volumes = json!({});
+
+    // Filesystem
+    if security.read_only_rootfs {
+        let options = format!("rw,exec,size={}", security.tmpfs_size);
+        host_config["Tmpfs"] = json!({ WORKDIR: options, "/tmp": options });
+        // Output files are copied out after the container stops, which a tmpfs won't survive
+        volumes[OUTPUT_DIR] = json!({});
+        // Give tools that write to the home directory somewhere writable
+        env.insert(0, "HOME=/tmp".into());
+    }
+
+    Ok(json!({
+        "Image": config.docker_image(),
+        "Cmd": ["bash", "-c", script],
+        "WorkingDir": WORKDIR,
+        "Env": env,
+        "User": security.user.clone().unwrap_or_default(),
+        // Labels for finding the container again if it gets orphaned
+        "Labels": {
+            INSTANCE_LABEL: *INSTANCE_ID,
+            LANGUAGE_LABEL: config.name(),
+            STARTED_LABEL: unix_time().to_string(),
+        },
+        "Volumes": volumes,
+        "AttachStdin": true,
+        "AttachStdout": true,
+        "AttachStderr": true,
+        "OpenStdin": true,
+        "StdinOnce": true,
+        "Tty": false,
+        "HostConfig": host_config,
+    }))
+}
+
+/// Force-removes sandbox containers from any instance of the bot that have outlived their
+/// language's timeout, returning how many were removed. Pool containers started by the docker
+/// backend are only removed once they have stopped.
+async fn reap_stale_containers(client: &DockerClient, languages: &LanguageOverrides) -> usize {
+    let containers = match client.list_containers(INSTANCE_LABEL).await {
+        Ok(containers) => containers,
+        Err(e) => {
+            tracing::error!("Failed to list sandbox containers: {e}");
+            return 0;
+        }
+    };
+
+    let now = unix_time();
+    let mut removed = 0;
+
+    for container in containers {
+        let label = |name| container.labels.get(name).map(String::as_str);
+        if is_stale(&container.state, label, languages, now) {
+            match client.remove(&container.id).await {
+                Ok(()) => removed += 1,
+                Err(e) => tracing::error!("Failed to remove container {}: {e}", container.id),
+            }
+        }
+    }
+
+    if removed > 0 {
+        tracing::info!("Removed {removed} orphaned sandbox containers");
+    } else {
+        tracing::debug!("No orphaned sandbox containers found");
+    }
+
+    removed
+}
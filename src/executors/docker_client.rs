@@ -0,0 +1,657 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{
+        UnixStream,
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+    },
+};
+
+use crate::utils::percent_encode;
+
+/// Engine API version requests are made against, which is supported from Docker 20.10 onwards.
+const API_VERSION: &str = "v1.41";
+
+/// How long a request that isn't expected to block is given before it is abandoned.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest response body read into memory, unless the caller sets a smaller limit.
+const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Client for the Docker Engine API served on a Unix socket. Each request is made on its own
+/// connection, so the client is cheap to clone and share.
+#[derive(Clone, Debug)]
+pub struct DockerClient {
+    socket: String,
+}
+
+/// Failure talking to the Docker daemon.
+#[derive(Debug)]
+pub enum DockerError {
+    /// The socket couldn't be reached or the connection broke.
+    Io(io::Error),
+    /// The daemon didn't answer in time.
+    Timeout,
+    /// The daemon refused the request with the given HTTP status and message.
+    Api { status: u16, message: String },
+    /// The daemon's response couldn't be understood.
+    InvalidResponse(String),
+}
+
+impl DockerError {
+    /// HTTP status of an error the daemon returned, such as 404 for a missing container.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DockerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Failed to reach the Docker daemon: {e}"),
+            Self::Timeout => write!(f, "Timeout while waiting for the Docker daemon"),
+            Self::Api { status, message } => {
+                write!(f, "Docker daemon returned {status}: {message}")
+            }
+            Self::InvalidResponse(reason) => {
+                write!(f, "Invalid response from the Docker daemon: {reason}")
+            }
+        }
+    }
+}
+
+impl Error for DockerError {}
+
+impl From<io::Error> for DockerError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// State of a container as reported by inspecting it.
+#[derive(Debug, Deserialize)]
+pub struct ContainerState {
+    /// The kernel killed something in the container for running out of memory.
+    #[serde(rename = "OOMKilled")]
+    pub oom_killed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerSummary {
+    pub id: String,
+    /// Such as `running` or `exited`.
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// The streams of a container attached to before it was started. Output arrives multiplexed,
+/// see [`demultiplex`], and shutting down the input closes the container's stdin.
+pub struct Attachment {
+    pub output: BufReader<OwnedReadHalf>,
+    pub input: OwnedWriteHalf,
+}
+
+struct ResponseHead {
+    status: u16,
+    /// Header values by lowercase name.
+    headers: HashMap<String, String>,
+}
+
+impl DockerClient {
+    pub fn new(socket: impl Into<String>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// Creates a container from the given configuration, returning its ID.
+    pub async fn create_container(
+        &self,
+        name: &str,
+        config: &Value,
+    ) -> Result<String, DockerError> {
+        #[derive(Deserialize)]
+        struct Created {
+            #[serde(rename = "Id")]
+            id: String,
+        }
+
+        let path = format!("/containers/create?name={}", percent_encode(name));
+        let created: Created = self.request_json("POST", &path, Some(config)).await?;
+
+        Ok(created.id)
+    }
+
+    /// Pulls the image, waiting for the pull to finish.
+    pub async fn pull_image(&self, image: &str) -> Result<(), DockerError> {
+        // Without a tag every tag of the image would be pulled
+        let name = image.rsplit('/').next().unwrap_or(image);
+        let image = match name.contains(':') || name.contains('@') {
+            true => image.to_string(),
+            false => format!("{image}:latest"),
+        };
+
+        let path = format!("/images/create?fromImage={}", percent_encode(&image));
+        let (head, mut reader, _) = self.send("POST", &path, None, false).await?;
+        let body = read_body(&head, &mut reader, MAX_RESPONSE_SIZE).await?;
+        check_status(&head, &body)?;
+
+        // Failures part way through are reported in the progress stream rather than the status
+        let error = String::from_utf8_lossy(&body)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find_map(|progress| progress.get("error")?.as_str().map(String::from));
+        match error {
+            Some(message) => Err(DockerError::Api {
+                status: head.status,
+                message,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Attaches to the container's stdin, stdout and stderr. Attaching before the container is
+    /// started ensures none of its output is missed.
+    pub async fn attach(&self, id: &str) -> Result<Attachment, DockerError> {
+        let path = format!("/containers/{id}/attach?stream=1&stdin=1&stdout=1&stderr=1");
+        let (head, mut output, input) =
+            tokio::time::timeout(REQUEST_TIMEOUT, self.send("POST", &path, None, true))
+                .await
+                .map_err(|_| DockerError::Timeout)??;
+
+        // Older daemons answer with 200 rather than switching protocols
+        if !matches!(head.status, 101 | 200) {
+            let body = read_body(&head, &mut output, MAX_RESPONSE_SIZE).await?;
+            check_status(&head, &body)?;
+        }
+
+        Ok(Attachment { output, input })
+    }
+
+    pub async fn start(&self, id: &str) -> Result<(), DockerError> {
+        self.request("POST", &format!("/containers/{id}/start"), None)
+            .await
+            .map(|_| ())
+    }
+
+    /// Waits for the container to stop, returning its exit code.
+    pub async fn wait(&self, id: &str) -> Result<i64, DockerError> {
+        #[derive(Deserialize)]
+        struct Waited {
+            #[serde(rename = "StatusCode")]
+            status_code: i64,
+        }
+
+        // Containers can run for as long as their language allows, so there's no timeout here
+        let path = format!("/containers/{id}/wait");
+        let (head, mut reader, _) = self.send("POST", &path, None, false).await?;
+        let body = read_body(&head, &mut reader, MAX_RESPONSE_SIZE).await?;
+        check_status(&head, &body)?;
+
+        let waited: Waited = serde_json::from_slice(&body)
+            .map_err(|e| DockerError::InvalidResponse(e.to_string()))?;
+        Ok(waited.status_code)
+    }
+
+    pub async fn inspect(&self, id: &str) -> Result<ContainerState, DockerError> {
+        #[derive(Deserialize)]
+        struct Details {
+            #[serde(rename = "State")]
+            state: ContainerState,
+        }
+
+        let details: Details = self
+            .request_json("GET", &format!("/containers/{id}/json"), None)
+            .await?;
+        Ok(details.state)
+    }
+
+    /// Sends the signal, such as `TERM`, to the container's main process.
+    pub async fn kill(&self, id: &str, signal: &str) -> Result<(), DockerError> {
+        let path = format!("/containers/{id}/kill?signal={}", percent_encode(signal));
+        self.request("POST", &path, None).await.map(|_| ())
+    }
+
+    /// Force-removes the container along with its anonymous volumes.
+    pub async fn remove(&self, id: &str) -> Result<(), DockerError> {
+        let path = format!("/containers/{id}?force=true&v=true");
+        self.request("DELETE", &path, None).await.map(|_| ())
+    }
+
+    /// Lists every container with the label, running or not.
+    pub async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>, DockerError> {
+        let filters = json!({ "label": [label] }).to_string();
+        let path = format!(
+            "/containers/json?all=true&filters={}",
+            percent_encode(&filters)
+        );
+        self.request_json("GET", &path, None).await
+    }
+
+    /// Downloads a tar archive of a path in the container, reading at most `limit` bytes of it.
+    pub async fn archive(
+        &self,
+        id: &str,
+        path: &str,
+        limit: usize,
+    ) -> Result<Vec<u8>, DockerError> {
+        let path = format!("/containers/{id}/archive?path={}", percent_encode(path));
+        let exchange = async {
+            let (head, mut reader, _) = self.send("GET", &path, None, false).await?;
+            let body = read_body(&head, &mut reader, limit).await?;
+            check_status(&head, &body)?;
+            Ok(body)
+        };
+
+        tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+            .await
+            .map_err(|_| DockerError::Timeout)?
+    }
+
+    async fn request_json<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<T, DockerError> {
+        let body = self.request(method, path, body).await?;
+        serde_json::from_slice(&body).map_err(|e| DockerError::InvalidResponse(e.to_string()))
+    }
+
+    /// Makes a request that is expected to be answered promptly, returning the response body if
+    /// it succeeded.
+    async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Vec<u8>, DockerError> {
+        let exchange = async {
+            let (head, mut reader, _) = self.send(method, path, body, false).await?;
+            let body = read_body(&head, &mut reader, MAX_RESPONSE_SIZE).await?;
+            check_status(&head, &body)?;
+            Ok(body)
+        };
+
+        tokio::time::timeout(REQUEST_TIMEOUT, exchange)
+            .await
+            .map_err(|_| DockerError::Timeout)?
+    }
+
+    /// Sends a request on a new connection and reads the head of the response, returning the
+    /// rest of the connection. An upgraded connection carries the container's streams after
+    /// the head rather than closing after a single response.
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+        upgrade: bool,
+    ) -> Result<(ResponseHead, BufReader<OwnedReadHalf>, OwnedWriteHalf), DockerError> {
+        let body = body.map(Value::to_string).unwrap_or_default();
+
+        let (reader, mut writer) = UnixStream::connect(&self.socket).await?.into_split();
+        let mut request = request_head(method, path, body.len(), upgrade).into_bytes();
+        request.extend(body.as_bytes());
+        writer.write_all(&request).await?;
+
+        let mut reader = BufReader::new(reader);
+        let head = read_head(&mut reader).await?;
+        Ok((head, reader, writer))
+    }
+}
+
+/// Splits the multiplexed output of an attached container into stdout and stderr, until the
+/// container closes its output or either destination stops accepting it. Each frame starts with
+/// an eight byte header holding the stream it belongs to and its length.
+pub async fn demultiplex(
+    mut output: impl AsyncRead + Unpin,
+    mut stdout: impl AsyncWrite + Unpin,
+    mut stderr: impl AsyncWrite + Unpin,
+) -> io::Result<()> {
+    let mut header = [0; 8];
+    loop {
+        match output.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        let mut frame = (&mut output).take(size);
+        match header[0] {
+            1 => io::copy(&mut frame, &mut stdout).await?,
+            2 => io::copy(&mut frame, &mut stderr).await?,
+            _ => io::copy(&mut frame, &mut io::sink()).await?,
+        };
+    }
+
+    stdout.shutdown().await?;
+    stderr.shutdown().await
+}
+
+fn request_head(method: &str, path: &str, content_length: usize, upgrade: bool) -> String {
+    let mut head = format!(
+        "{method} /{API_VERSION}{path} HTTP/1.1\r\nHost: docker\r\nUser-Agent: compiler-bot\r\n"
+    );
+    match upgrade {
+        true => head.push_str("Connection: Upgrade\r\nUpgrade: tcp\r\n"),
+        false => head.push_str("Connection: close\r\n"),
+    }
+    if content_length > 0 {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    if content_length > 0 || method == "POST" {
+        head.push_str(&format!("Content-Length: {content_length}\r\n"));
+    }
+    head.push_str("\r\n");
+
+    head
+}
+
+async fn read_head(
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+) -> Result<ResponseHead, DockerError> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| DockerError::InvalidResponse(format!("Bad status line: {line:?}")))?;
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(DockerError::InvalidResponse("Truncated headers".into()));
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            break;
+        };
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    Ok(ResponseHead { status, headers })
+}
+
+/// Reads the response body, decoding chunked transfer encoding, and stops after `limit` bytes.
+async fn read_body(
+    head: &ResponseHead,
+    reader: &mut (impl AsyncBufReadExt + Unpin),
+    limit: usize,
+) -> Result<Vec<u8>, DockerError> {
+    let mut body = Vec::new();
+
+    if head
+        .headers
+        .get("transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        let mut line = String::new();
+        while body.len() < limit {
+            line.clear();
+            reader.read_line(&mut line).await?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .map_err(|_| DockerError::InvalidResponse(format!("Bad chunk size: {line:?}")))?;
+            if size == 0 {
+                break;
+            }
+
+            let wanted = size.min(limit - body.len());
+            (&mut *reader)
+                .take(wanted as u64)
+                .read_to_end(&mut body)
+                .await?;
+            if wanted < size {
+                break;
+            }
+
+            line.clear();
+            reader.read_line(&mut line).await?;
+        }
+    } else {
+        let length = head
+            .headers
+            .get("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap_or(usize::MAX);
+        (&mut *reader)
+            .take(length.min(limit) as u64)
+            .read_to_end(&mut body)
+            .await?;
+    }
+
+    Ok(body)
+}
+
+/// Turns an unsuccessful response into an error carrying the daemon's message.
+fn check_status(head: &ResponseHead, body: &[u8]) -> Result<(), DockerError> {
+    if head.status < 300 || head.status == 304 {
+        return Ok(());
+    }
+
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|error| error.get("message")?.as_str().map(String::from))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+
+    Err(DockerError::Api {
+        status: head.status,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use tokio::net::UnixListener;
+    use uuid::Uuid;
+
+    use super::*;
+
+    async fn head(response: &str) -> Result<ResponseHead, DockerError> {
+        read_head(&mut BufReader::new(response.as_bytes())).await
+    }
+
+    async fn body(response: &str, limit: usize) -> Result<Vec<u8>, DockerError> {
+        let mut reader = BufReader::new(response.as_bytes());
+        let head = read_head(&mut reader).await?;
+        read_body(&head, &mut reader, limit).await
+    }
+
+    fn frame(stream: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend((payload.len() as u32).to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    /// Serves a single canned response on a fresh socket, handing back the request it got.
+    async fn serve_once(response: &'static str) -> (DockerClient, tokio::task::JoinHandle<String>) {
+        let socket = env::temp_dir().join(format!("compiler-bot-test-{}.sock", Uuid::new_v4()));
+        let listener = UnixListener::bind(&socket).unwrap();
+        let client = DockerClient::new(socket.to_string_lossy());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = fs::remove_file(&socket);
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+
+            let mut request = String::new();
+            while !request.ends_with("\r\n\r\n") {
+                if reader.read_line(&mut request).await.unwrap() == 0 {
+                    break;
+                }
+            }
+            writer.write_all(response.as_bytes()).await.unwrap();
+            request
+        });
+
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn heads_are_parsed() {
+        let head = head("HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(head.status, 404);
+        assert_eq!(head.headers["content-type"], "application/json");
+    }
+
+    #[tokio::test]
+    async fn truncated_heads_are_refused() {
+        assert!(matches!(
+            head("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n").await,
+            Err(DockerError::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            head("").await,
+            Err(DockerError::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            head("garbage\r\n\r\n").await,
+            Err(DockerError::InvalidResponse(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn chunked_bodies_are_decoded() {
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n";
+        assert_eq!(body(response, 1024).await.unwrap(), b"hello, world");
+
+        // Reading stops at the limit, even part way through a chunk
+        assert_eq!(body(response, 8).await.unwrap(), b"hello, w");
+    }
+
+    #[tokio::test]
+    async fn bad_chunk_sizes_are_refused() {
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(matches!(
+            body(response, 1024).await,
+            Err(DockerError::InvalidResponse(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sized_bodies_stop_at_their_length_or_the_limit() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello, world";
+        assert_eq!(body(response, 1024).await.unwrap(), b"hello");
+        assert_eq!(body(response, 3).await.unwrap(), b"hel");
+
+        // Without a length the body runs until the connection closes
+        let response = "HTTP/1.1 200 OK\r\n\r\nhello, world";
+        assert_eq!(body(response, 1024).await.unwrap(), b"hello, world");
+    }
+
+    #[tokio::test]
+    async fn frames_split_across_reads_are_demultiplexed() {
+        let mut output = frame(1, b"hello ");
+        output.extend(frame(2, b"oops"));
+        output.extend(frame(0, b"stdin echo"));
+        output.extend(frame(1, b"world"));
+
+        // A tiny pipe hands the frames over a few bytes at a time, splitting headers too
+        let (mut writer, reader) = io::duplex(3);
+        tokio::spawn(async move { writer.write_all(&output).await });
+
+        let (stdout_writer, mut stdout) = io::duplex(1024);
+        let (stderr_writer, mut stderr) = io::duplex(1024);
+        demultiplex(reader, stdout_writer, stderr_writer)
+            .await
+            .unwrap();
+
+        let (mut stdout_text, mut stderr_text) = (String::new(), String::new());
+        stdout.read_to_string(&mut stdout_text).await.unwrap();
+        stderr.read_to_string(&mut stderr_text).await.unwrap();
+        assert_eq!(stdout_text, "hello world");
+        assert_eq!(stderr_text, "oops");
+    }
+
+    #[tokio::test]
+    async fn truncated_frames_end_the_output() {
+        let mut output = frame(1, b"hello");
+        output.extend(&frame(1, b"world")[..6]);
+
+        let (stdout_writer, mut stdout) = io::duplex(1024);
+        demultiplex(output.as_slice(), stdout_writer, io::sink())
+            .await
+            .unwrap();
+
+        let mut text = String::new();
+        stdout.read_to_string(&mut text).await.unwrap();
+        assert_eq!(text, "hello");
+    }
+
+    #[tokio::test]
+    async fn requests_are_made_over_the_socket() {
+        let (client, server) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n\
+             f\r\n{\"State\":{\"OOMK\r\n\
+             b\r\nilled\":true\r\n\
+             3\r\n}}\n\r\n0\r\n\r\n",
+        )
+        .await;
+
+        let state = client.inspect("abc").await.unwrap();
+        assert!(state.oom_killed);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with(&format!(
+            "GET /{API_VERSION}/containers/abc/json HTTP/1.1\r\n"
+        )));
+        assert!(request.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn api_errors_carry_the_daemons_message() {
+        let (client, _server) = serve_once(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 38\r\n\r\n\
+             {\"message\":\"No such container: abc\"}\n",
+        )
+        .await;
+
+        let error = client.kill("abc", "TERM").await.unwrap_err();
+        assert_eq!(error.status(), Some(404));
+        assert_eq!(
+            error.to_string(),
+            "Docker daemon returned 404: No such container: abc"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use async_trait::async_trait;
+
+use super::{ExecutionRequest, ExecutionResult, ExecutionStatus, Executor, ResourceUsage};
+use crate::runners::LANGUAGES;
+
+/// Backend that runs nothing, so commands can be tried out and tested without a sandbox.
+///
+/// Requests are answered with the outcomes it was scripted with, in order, such as a timeout or
+/// an executor error. Once those run out, the program's output is its stdin followed by a line
+/// for each file it was given, and it always succeeds.
+#[derive(Default)]
+pub struct MockExecutor {
+    script: Mutex<VecDeque<Result<ExecutionResult, String>>>,
+    /// Every request received, oldest first.
+    requests: Mutex<Vec<ExecutionRequest>>,
+}
+
+impl MockExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers the next requests with these outcomes before going back to echoing.
+    #[cfg(test)]
+    pub fn scripted(outcomes: impl IntoIterator<Item = Result<ExecutionResult, String>>) -> Self {
+        Self {
+            script: Mutex::new(outcomes.into_iter().collect()),
+            ..Self::default()
+        }
+    }
+
+    /// The requests received so far, oldest first.
+    #[cfg(test)]
+    pub fn requests(&self) -> Vec<ExecutionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Executor for MockExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        self.requests.lock().unwrap().push(request.clone());
+        if let Some(outcome) = self.script.lock().unwrap().pop_front() {
+            return outcome;
+        }
+
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+
+        let config = LANGUAGES
+            .get(request.language.as_str())
+            .ok_or_else(|| format!("Unsupported language: {}", request.language))?;
+
+        let mut stdout = request.stdin.clone();
+        for file in &request.files {
+            let name = match request.files.as_slice() {
+                [_] => config.source_file(),
+                _ => file.name.clone(),
+            };
+            stdout.push_str(&format!("{name} ({} bytes)\n", file.contents.len()));
+        }
+
+        Ok(ExecutionResult {
+            stdout,
+            stderr: String::new(),
+            exit_code: Some(0),
+            status: ExecutionStatus::Completed,
+            files: Vec::new(),
+            usage: ResourceUsage::default(),
+        })
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        LANGUAGES.keys().copied().collect()
+    }
+}
@@ -0,0 +1,500 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    os::unix::process::ExitStatusExt,
+    path::{Component, Path},
+    process::ExitStatus,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub use self::{
+    docker::{ContainerEngine, DockerExecutor},
+    docker_api::DockerApiExecutor,
+    mock::MockExecutor,
+    nsjail::NsjailExecutor,
+    piston::PistonExecutor,
+    remote::RemoteExecutor,
+};
+#[cfg(feature = "wasi")]
+pub use self::{router::Router, wasi::WasiExecutor};
+use crate::config::{Backend, BotConfig, InputConfig};
+
+mod capture;
+#[cfg(test)]
+mod conformance;
+mod container_pool;
+mod docker;
+mod docker_api;
+mod docker_client;
+mod mock;
+mod nsjail;
+mod piston;
+mod remote;
+#[cfg(feature = "wasi")]
+mod router;
+mod script;
+#[cfg(feature = "wasi")]
+mod wasi;
+
+/// Runs source files in a sandbox. Each execution backend implements this, and the one used is
+/// picked from the configuration by [`from_config`].
+#[async_trait]
+pub trait Executor: Send + Sync {
+    /// Runs the request's files. A single file is saved under the language's usual source file
+    /// name, while several files are built as a project whose entry point is the first file with
+    /// the language's extension.
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String>;
+
+    fn supported_languages(&self) -> Vec<&'static str>;
+}
+
+/// Creates the executor for the configured backend, starting any background tasks it needs.
+/// Plain Python snippets are routed to the WASI interpreter instead when one is configured.
+pub fn from_config(config: &BotConfig) -> Arc<dyn Executor> {
+    let backend: Arc<dyn Executor> = match config.backend {
+        Backend::Docker => Arc::new(DockerExecutor::from_config(config, ContainerEngine::Docker)),
+        Backend::Podman => Arc::new(DockerExecutor::from_config(config, ContainerEngine::Podman)),
+        Backend::DockerApi => Arc::new(DockerApiExecutor::from_config(config)),
+        Backend::Nsjail => Arc::new(NsjailExecutor::from_config(config)),
+        Backend::Piston => Arc::new(PistonExecutor::from_config(config)),
+        Backend::Remote => Arc::new(RemoteExecutor::from_config(config)),
+        Backend::Mock => Arc::new(MockExecutor::new()),
+    };
+
+    with_router(config, backend)
+}
+
+/// Creates the executor for the configured backend like [`from_config`], but without the
+/// container pool, the cleanup of orphaned containers or worker health checks, which would only
+/// outlive a single run.
+pub fn for_single_run(config: &BotConfig) -> Arc<dyn Executor> {
+    let backend: Arc<dyn Executor> = match config.backend {
+        Backend::Docker => Arc::new(DockerExecutor::with_config(config, ContainerEngine::Docker)),
+        Backend::Podman => Arc::new(DockerExecutor::with_config(config, ContainerEngine::Podman)),
+        Backend::DockerApi => Arc::new(DockerApiExecutor::with_config(config)),
+        Backend::Nsjail => Arc::new(NsjailExecutor::from_config(config)),
+        Backend::Piston => Arc::new(PistonExecutor::from_config(config)),
+        Backend::Remote => Arc::new(RemoteExecutor::with_config(config)),
+        Backend::Mock => Arc::new(MockExecutor::new()),
+    };
+
+    with_router(config, backend)
+}
+
+/// Routes plain Python snippets to the WASI interpreter when one is configured.
+fn with_router(config: &BotConfig, backend: Arc<dyn Executor>) -> Arc<dyn Executor> {
+    #[cfg(feature = "wasi")]
+    if let Some(module) = &config.wasi.python {
+        match WasiExecutor::from_config(config, module) {
+            Ok(wasi) => return Arc::new(Router::new(backend, wasi)),
+            Err(e) => tracing::error!("{e}, running every Python snippet in the backend"),
+        }
+    }
+    #[cfg(not(feature = "wasi"))]
+    if config.wasi.python.is_some() {
+        tracing::warn!("Ignoring wasi.python, the bot was built without the wasi feature");
+    }
+
+    backend
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExecutionRequest {
+    pub language: String,
+    pub files: Vec<SourceFile>,
+    pub stdin: String,
+}
+
+impl ExecutionRequest {
+    /// Checks the request against the input limits.
+    pub fn validate(&self, input: &InputConfig) -> Result<(), String> {
+        if self.files.len() > input.max_files {
+            return Err(format!(
+                "Too many files, at most {} are allowed",
+                input.max_files
+            ));
+        }
+
+        let source_size = self
+            .files
+            .iter()
+            .map(|file| file.contents.len())
+            .sum::<usize>();
+        if source_size > input.max_source_size {
+            return Err(format!(
+                "Source code exceeds the {} byte limit",
+                input.max_source_size
+            ));
+        }
+
+        if self.stdin.len() > input.max_stdin_size {
+            return Err(format!(
+                "Input exceeds the {} byte limit",
+                input.max_stdin_size
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Whether there is no code to run at all.
+    pub fn is_empty(&self) -> bool {
+        self.files
+            .iter()
+            .all(|file| file.contents.trim().is_empty())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SourceFile {
+    pub name: String,
+    pub contents: String,
+}
+
+impl SourceFile {
+    /// Checks that the file name is a plain relative path that stays inside the working
+    /// directory and is safe to quote in a shell command.
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        let is_relative = Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        let is_plain = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+
+        if name.is_empty() || name.len() > 255 || !is_relative || !is_plain {
+            return Err(format!("Invalid file name: {name:?}"));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub status: ExecutionStatus,
+    pub files: Vec<OutputFile>,
+    pub usage: ResourceUsage,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    /// The program ran to completion, whether it succeeded or not.
+    Completed,
+    TimedOut,
+    /// The program was killed for printing more than the capture limit allows.
+    OutputLimitExceeded,
+    /// The kernel killed the program for going over the container's memory limit.
+    OutOfMemory {
+        memory_limit: String,
+    },
+    /// The program was terminated by the given signal.
+    Signaled(i32),
+}
+
+impl ExecutionStatus {
+    /// Explains how the program ended, unless it simply exited.
+    pub fn description(&self) -> Option<String> {
+        match self {
+            Self::Completed => None,
+            Self::TimedOut => Some("Execution timed out".into()),
+            Self::OutputLimitExceeded => Some("Output limit exceeded".into()),
+            Self::OutOfMemory { memory_limit } => {
+                Some(format!("Memory limit {memory_limit} exceeded"))
+            }
+            Self::Signaled(signal) => Some(match signal {
+                4 => "Illegal instruction (SIGILL)".into(),
+                5 => "Trace/breakpoint trap (SIGTRAP)".into(),
+                6 => "Aborted (assertion failed)".into(),
+                7 => "Bus error (SIGBUS)".into(),
+                8 => "Floating point exception (SIGFPE)".into(),
+                9 => "Killed (SIGKILL)".into(),
+                11 => "Segmentation fault (SIGSEGV)".into(),
+                13 => "Broken pipe (SIGPIPE)".into(),
+                15 => "Terminated (SIGTERM)".into(),
+                24 => "CPU time limit exceeded (SIGXCPU)".into(),
+                25 => "File size limit exceeded (SIGXFSZ)".into(),
+                signal => format!("Killed by signal {signal}"),
+            }),
+        }
+    }
+
+    /// Works out why a container exited unsuccessfully, from the OOM killer's verdict and the
+    /// signal encoded in its exit status.
+    pub(crate) fn from_exit(exit_status: ExitStatus, oom_killed: bool, memory_limit: &str) -> Self {
+        if oom_killed {
+            return Self::OutOfMemory {
+                memory_limit: memory_limit.into(),
+            };
+        }
+
+        // Shells report a process killed by signal N as exiting with 128 + N
+        let signal = exit_status.signal().or_else(|| {
+            exit_status
+                .code()
+                .filter(|code| (129..=192).contains(code))
+                .map(|code| code - 128)
+        });
+
+        match signal {
+            Some(signal) => Self::Signaled(signal),
+            None => Self::Completed,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    #[serde(rename = "wall_time_ms", with = "millis")]
+    pub wall_time: Duration,
+    /// User and system CPU time of everything run in the container, including the build.
+    #[serde(rename = "cpu_time_ms", with = "millis::option")]
+    pub cpu_time: Option<Duration>,
+    /// Peak memory of the container in bytes, including the build.
+    pub peak_memory: Option<u64>,
+}
+
+impl ResourceUsage {
+    /// Splits the usage report a run writes after its marker off the end of stderr.
+    pub(crate) fn split_report(stderr: &mut String, marker: &str) -> Option<Self> {
+        let start = stderr.rfind(&format!("\n{marker} "))?;
+        let report = stderr.split_off(start);
+
+        let mut fields = report.split_whitespace().skip(1);
+        let mut next_number = || fields.next().and_then(|field| field.parse::<u64>().ok());
+
+        Some(Self {
+            wall_time: Duration::from_nanos(next_number()?),
+            cpu_time: next_number().map(Duration::from_micros),
+            peak_memory: next_number(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutputFile {
+    /// Path of the file relative to the output directory.
+    pub name: String,
+    #[serde(with = "base64_bytes")]
+    pub contents: Vec<u8>,
+}
+
+impl ExecutionResult {
+    /// Result for a request with no code in it, reported like a failed run.
+    pub fn empty_code() -> Self {
+        Self {
+            stdout: String::new(),
+            stderr: "Error: Code is empty or contains only whitespace.".into(),
+            exit_code: Some(1),
+            status: ExecutionStatus::Completed,
+            files: Vec::new(),
+            usage: ResourceUsage::default(),
+        }
+    }
+}
+
+/// Parses a size in Docker's notation, such as `256m`, into bytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_lowercase();
+    let (number, multiplier) = match size.char_indices().last()? {
+        (index, 'b') => (&size[..index], 1),
+        (index, 'k') => (&size[..index], 1 << 10),
+        (index, 'm') => (&size[..index], 1 << 20),
+        (index, 'g') => (&size[..index], 1 << 30),
+        _ => (size.as_str(), 1),
+    };
+
+    number.parse::<u64>().ok().map(|number| number * multiplier)
+}
+
+/// Serializes bytes as a base64 string, which is far more compact in JSON than an array.
+mod base64_bytes {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes a duration as whole milliseconds, rather than serde's seconds and nanoseconds.
+mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+
+    pub mod option {
+        use std::time::Duration;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match duration {
+                Some(duration) => super::serialize(duration, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            Option::<u64>::deserialize(deserializer).map(|millis| millis.map(Duration::from_millis))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exited(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[test]
+    fn from_exit_maps_shell_exit_codes_to_signals() {
+        assert_eq!(
+            ExecutionStatus::from_exit(exited(139), false, "256m"),
+            ExecutionStatus::Signaled(11)
+        );
+        assert_eq!(
+            ExecutionStatus::from_exit(exited(134), false, "256m"),
+            ExecutionStatus::Signaled(6)
+        );
+        assert_eq!(
+            ExecutionStatus::from_exit(exited(137), false, "256m"),
+            ExecutionStatus::Signaled(9)
+        );
+    }
+
+    #[test]
+    fn from_exit_maps_raw_signals() {
+        assert_eq!(
+            ExecutionStatus::from_exit(ExitStatus::from_raw(8), false, "256m"),
+            ExecutionStatus::Signaled(8)
+        );
+    }
+
+    #[test]
+    fn from_exit_leaves_ordinary_failures_alone() {
+        for code in [1, 2, 124, 128, 193, 255] {
+            assert_eq!(
+                ExecutionStatus::from_exit(exited(code), false, "256m"),
+                ExecutionStatus::Completed
+            );
+        }
+    }
+
+    #[test]
+    fn from_exit_prefers_the_oom_killer() {
+        assert_eq!(
+            ExecutionStatus::from_exit(exited(137), true, "256m"),
+            ExecutionStatus::OutOfMemory {
+                memory_limit: "256m".into()
+            }
+        );
+    }
+
+    #[test]
+    fn descriptions_name_common_signals() {
+        assert_eq!(
+            ExecutionStatus::Signaled(11).description().unwrap(),
+            "Segmentation fault (SIGSEGV)"
+        );
+        assert_eq!(
+            ExecutionStatus::Signaled(42).description().unwrap(),
+            "Killed by signal 42"
+        );
+        assert_eq!(ExecutionStatus::Completed.description(), None);
+    }
+
+    #[test]
+    fn usage_durations_are_milliseconds() {
+        let usage = ResourceUsage {
+            wall_time: Duration::from_micros(1_234_567),
+            cpu_time: Some(Duration::from_millis(890)),
+            peak_memory: Some(4096),
+        };
+        let json = serde_json::to_value(&usage).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "wall_time_ms": 1234, "cpu_time_ms": 890, "peak_memory": 4096 })
+        );
+
+        let usage = serde_json::from_value::<ResourceUsage>(
+            serde_json::json!({ "wall_time_ms": 5, "cpu_time_ms": null, "peak_memory": null }),
+        )
+        .unwrap();
+        assert_eq!(usage.wall_time, Duration::from_millis(5));
+        assert_eq!(usage.cpu_time, None);
+    }
+
+    #[test]
+    fn parse_size_understands_docker_suffixes() {
+        assert_eq!(parse_size("256m"), Some(256 << 20));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("512k"), Some(512 << 10));
+        assert_eq!(parse_size("100b"), Some(100));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("lots"), None);
+    }
+
+    #[test]
+    fn single_runs_start_no_background_tasks() {
+        // Outside a runtime, spawning a task would panic
+        let mut config = BotConfig::default();
+        config.pool.size = 2;
+        for backend in [
+            Backend::Docker,
+            Backend::Podman,
+            Backend::DockerApi,
+            Backend::Nsjail,
+            Backend::Piston,
+            Backend::Remote,
+            Backend::Mock,
+        ] {
+            config.backend = backend;
+            assert!(!for_single_run(&config).supported_languages().is_empty());
+        }
+    }
+}
@@ -0,0 +1,457 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::{io::AsyncWriteExt, process::Command};
+use uuid::Uuid;
+
+use super::{
+    ExecutionRequest, ExecutionResult, Executor,
+    capture::{self, OutputCapture},
+    parse_size,
+    script::{self, OUTPUT_DIR, WORKDIR},
+};
+use crate::{
+    config::{
+        BUILTIN_SECCOMP, BotConfig, InputConfig, LanguageOverrides, NsjailConfig, OutputConfig,
+        SecurityConfig,
+    },
+    runners::{LANGUAGES, Language},
+};
+
+/// Seccomp policy applied by default, built in so it is found wherever the bot runs from.
+const SECCOMP_POLICY: &str = include_str!("../../nsjail/seccomp.kafel");
+
+/// Prefix of the cgroups and working directories made for each job.
+const JOB_PREFIX: &str = "job-";
+
+/// Cgroup v2 controllers the job cgroups are limited with.
+const CONTROLLERS: &str = "+cpu +memory +pids";
+
+/// `PATH` inside the sandbox, which otherwise starts with an empty environment.
+const PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Runs each job with nsjail on the host's own toolchains, for hosts without a container engine.
+///
+/// The sandbox gets its own user, mount, PID, IPC, UTS, cgroup and network namespaces, sees only
+/// the configured host directories read-only, and is confined by a seccomp policy. Each job also
+/// runs in its own cgroup under the delegated `cgroup_root`, which enforces the CPU, memory and
+/// process limits and is mounted as the sandbox's `/sys/fs/cgroup` for the usage report.
+pub struct NsjailExecutor {
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub languages: LanguageOverrides,
+    pub nsjail: NsjailConfig,
+}
+
+impl NsjailExecutor {
+    /// Creates the executor with the configured limits, enabling the controllers the job cgroups
+    /// need and cleaning up after jobs left behind by a crash.
+    pub fn from_config(config: &BotConfig) -> Self {
+        let nsjail = config.nsjail.clone();
+
+        let subtree_control = Path::new(&nsjail.cgroup_root).join("cgroup.subtree_control");
+        if let Err(e) = fs::write(&subtree_control, CONTROLLERS) {
+            tracing::error!("Failed to enable cgroup controllers in {subtree_control:?}: {e}");
+        }
+
+        for root in [&nsjail.cgroup_root, &nsjail.work_dir] {
+            let Ok(entries) = fs::read_dir(root) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with(JOB_PREFIX) {
+                    Job::remove_dir(&entry.path());
+                }
+            }
+        }
+
+        Self {
+            input: config.input.clone(),
+            output: config.output.clone(),
+            languages: config.languages.clone(),
+            nsjail,
+        }
+    }
+
+    /// Builds the nsjail arguments that run the script in a fresh sandbox, deriving every limit
+    /// it enforces itself from the language's security configuration.
+    fn nsjail_args(
+        &self,
+        job: &Job,
+        config: &(dyn Language + Send + Sync),
+        security: &SecurityConfig,
+        script: &str,
+    ) -> Vec<String> {
+        let mut args = vec![
+            "--mode".into(),
+            "o".into(),
+            "--quiet".into(),
+            "--hostname".into(),
+            "sandbox".into(),
+            // Timeouts are handled here, this is only a backstop
+            "--time_limit".into(),
+            (security.timeout_duration + security.termination_grace_period + 10).to_string(),
+        ];
+
+        // Resource limits, other than the ones the cgroup enforces
+        let file_descriptors = security
+            .file_descriptor_limit
+            .split(':')
+            .next()
+            .unwrap_or_default();
+        args.extend([
+            "--rlimit_nofile".into(),
+            file_descriptors.into(),
+            "--rlimit_as".into(),
+            "inf".into(),
+            "--rlimit_cpu".into(),
+            "inf".into(),
+            "--rlimit_fsize".into(),
+            "inf".into(),
+        ]);
+
+        // Network access, there is none in a fresh network namespace
+        if !security.disable_network {
+            args.push("--disable_clone_newnet".into());
+        }
+
+        // Privileges, every capability is dropped unless kept here
+        for capability in &security.cap_add {
+            let capability = capability.to_uppercase();
+            let capability = match capability.starts_with("CAP_") {
+                true => capability,
+                false => format!("CAP_{capability}"),
+            };
+            args.extend(["--cap".into(), capability]);
+        }
+        match self.nsjail.seccomp_policy.as_deref() {
+            Some(BUILTIN_SECCOMP) => {
+                args.extend(["--seccomp_string".into(), SECCOMP_POLICY.into()]);
+            }
+            Some(policy) => args.extend(["--seccomp_policy".into(), policy.into()]),
+            None => {}
+        }
+        if let Some((uid, gid)) = security
+            .user
+            .as_deref()
+            .and_then(|user| user.split_once(':'))
+        {
+            // Map the sandbox user to the bot's own user, which owns the output directory
+            let (host_uid, host_gid) = fs::metadata("/proc/self")
+                .map(|metadata| (metadata.uid(), metadata.gid()))
+                .unwrap_or_default();
+            args.extend([
+                "--user".into(),
+                format!("{uid}:{host_uid}:1"),
+                "--group".into(),
+                format!("{gid}:{host_gid}:1"),
+            ]);
+        }
+
+        // Filesystem, an empty read-only root with the toolchains mounted read-only
+        for path in &self.nsjail.bind_mounts {
+            if Path::new(path).exists() {
+                args.extend(["--bindmount_ro".into(), path.clone()]);
+            }
+        }
+        for path in [WORKDIR, "/tmp"] {
+            args.extend([
+                "--mount".into(),
+                format!("none:{path}:tmpfs:size={}", security.tmpfs_size),
+            ]);
+        }
+        args.extend([
+            "--bindmount".into(),
+            format!("{}:{OUTPUT_DIR}", job.output_dir().display()),
+            "--bindmount_ro".into(),
+            format!("{}:/sys/fs/cgroup", job.cgroup.display()),
+        ]);
+
+        args.extend(["--cwd".into(), WORKDIR.into()]);
+        args.extend(["--env".into(), format!("PATH={PATH}")]);
+        args.extend(["--env".into(), "HOME=/tmp".into()]);
+        for (key, value) in config.environment() {
+            args.extend(["--env".into(), format!("{key}={value}")]);
+        }
+
+        args.extend(["--".into(), "/bin/bash".into(), "-c".into(), script.into()]);
+
+        args
+    }
+}
+
+#[async_trait]
+impl Executor for NsjailExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        let ExecutionRequest {
+            language,
+            files,
+            stdin,
+        } = request;
+        let language = language.as_str();
+
+        // Validate input
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+        request.validate(&self.input)?;
+
+        let config = LANGUAGES
+            .get(language)
+            .ok_or_else(|| format!("Unsupported language: {language}"))?;
+
+        let (files, command) = script::prepare_files(files, config.as_ref())?;
+        let script = script::run_script(&command);
+
+        let security = self.languages.security_config(config.as_ref());
+        let job = Job::create(&self.nsjail, &security)?;
+
+        // Move into the job's cgroup before becoming nsjail, so everything it starts is limited
+        let mut nsjail_cmd = Command::new("sh");
+        nsjail_cmd
+            .args(["-c", r#"echo $$ > "$0" && exec "$@""#])
+            .arg(job.cgroup.join("cgroup.procs"))
+            .arg(&self.nsjail.path)
+            .args(self.nsjail_args(&job, config.as_ref(), &security, &script))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        tracing::info!("Executing nsjail for language: {language}");
+
+        // Start the process
+        let marker = Uuid::new_v4().to_string();
+        let started_at = Instant::now();
+        let mut child = match nsjail_cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                job.remove().await;
+                return Err(format!("Failed to spawn nsjail: {e}"));
+            }
+        };
+        let nsjail_pid = child.id();
+
+        // Write code to stdin
+        if let Some(mut child_stdin) = child.stdin.take() {
+            let payload = script::payload(&marker, config.as_ref(), &files);
+            if let Err(e) = child_stdin.write_all(&payload).await {
+                tracing::error!("Failed to write to stdin: {e}");
+                job.kill();
+                let _ = child.wait().await;
+                job.remove().await;
+                return Err(format!("Failed to write code to sandbox: {e}"));
+            }
+
+            // Feed the program's input separately so a program that writes before reading
+            // cannot deadlock against us, then close stdin to signal EOF
+            let stdin = stdin.to_owned();
+            tokio::spawn(async move {
+                if let Err(e) = child_stdin.write_all(stdin.as_bytes()).await {
+                    tracing::debug!("Program did not consume all of its input: {e}");
+                }
+            });
+        }
+
+        let (Some(mut stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take())
+        else {
+            job.kill();
+            let _ = child.wait().await;
+            job.remove().await;
+            return Err("Failed to capture the sandbox's output".into());
+        };
+
+        // Wait for execution with timeout, killing the sandbox early if it floods its output
+        let mut capture = OutputCapture::new(self.output.max_capture_size);
+        let signal = |signal| {
+            let job = &job;
+            async move {
+                match signal {
+                    "KILL" => job.kill(),
+                    // nsjail kills the sandbox outright when it gets SIGTERM, so only the
+                    // sandbox's own processes are asked to stop
+                    signal => job.signal(signal, nsjail_pid).await,
+                }
+            }
+        };
+        let outcome = capture::supervise(
+            &mut child,
+            &mut capture,
+            &mut stdout,
+            &mut stderr,
+            &security,
+            signal,
+        )
+        .await;
+
+        let mut result = match outcome {
+            Ok(outcome) => Ok(outcome
+                .into_result(
+                    capture,
+                    &marker,
+                    started_at.elapsed(),
+                    async { job.was_oom_killed() },
+                    &security,
+                )
+                .await),
+            Err(e) => {
+                job.kill();
+                Err(format!("Process execution failed: {e}"))
+            }
+        };
+
+        if let Ok(result) = &mut result {
+            result.files = capture::read_output_dir(&job.output_dir(), &self.output);
+        }
+        job.remove().await;
+
+        result
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        LANGUAGES.keys().copied().collect()
+    }
+}
+
+/// The cgroup and host directory made for a single run.
+struct Job {
+    cgroup: PathBuf,
+    work_dir: PathBuf,
+}
+
+impl Job {
+    /// Creates the job's cgroup with the CPU, memory and process limits applied, along with its
+    /// output directory.
+    fn create(nsjail: &NsjailConfig, security: &SecurityConfig) -> Result<Self, String> {
+        let name = format!("{JOB_PREFIX}{}", Uuid::new_v4());
+        let job = Self {
+            cgroup: Path::new(&nsjail.cgroup_root).join(&name),
+            work_dir: Path::new(&nsjail.work_dir).join(&name),
+        };
+
+        let memory_limit = parse_size(&security.memory_limit)
+            .ok_or_else(|| format!("Invalid memory limit: {}", security.memory_limit))?;
+        let cpu_limit = security
+            .cpu_limit
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid CPU limit: {}", security.cpu_limit))?;
+        let limits = [
+            ("memory.max", memory_limit.to_string()),
+            ("memory.swap.max", "0".into()),
+            ("pids.max", security.pids_limit.to_string()),
+            (
+                "cpu.max",
+                format!("{} 100000", (cpu_limit * 100000.0) as u64),
+            ),
+        ];
+
+        let created = fs::create_dir(&job.cgroup)
+            .and_then(|_| {
+                limits
+                    .iter()
+                    .try_for_each(|(file, value)| fs::write(job.cgroup.join(file), value))
+            })
+            .and_then(|_| fs::create_dir_all(job.output_dir()));
+        if let Err(e) = created {
+            Self::remove_dir(&job.cgroup);
+            Self::remove_dir(&job.work_dir);
+            return Err(format!("Failed to set up the sandbox: {e}"));
+        }
+
+        Ok(job)
+    }
+
+    fn output_dir(&self) -> PathBuf {
+        self.work_dir.join("out")
+    }
+
+    /// Sends the signal to every process in the job except nsjail itself.
+    async fn signal(&self, signal: &str, except: Option<u32>) {
+        let procs = fs::read_to_string(self.cgroup.join("cgroup.procs")).unwrap_or_default();
+        let pids = procs
+            .lines()
+            .filter(|pid| pid.parse::<u32>().ok() != except)
+            .collect::<Vec<_>>();
+        if pids.is_empty() {
+            return;
+        }
+
+        tracing::warn!(
+            "Attempting to send SIG{signal} to sandbox: {:?}",
+            self.cgroup
+        );
+        let kill_result = Command::new("kill")
+            .args(["-s", signal])
+            .args(pids)
+            .output()
+            .await;
+        if let Err(e) = kill_result {
+            tracing::error!("Error executing kill: {e}");
+        }
+    }
+
+    /// Kills everything in the job, nsjail included.
+    fn kill(&self) {
+        if let Err(e) = fs::write(self.cgroup.join("cgroup.kill"), "1") {
+            tracing::error!("Failed to kill sandbox {:?}: {e}", self.cgroup);
+        }
+    }
+
+    /// Checks whether the kernel killed anything in the job for running out of memory.
+    fn was_oom_killed(&self) -> bool {
+        fs::read_to_string(self.cgroup.join("memory.events"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.strip_prefix("oom_kill "))
+            .any(|count| count.trim() != "0")
+    }
+
+    /// Removes the job's cgroup and output directory, waiting for the cgroup to empty first.
+    async fn remove(&self) {
+        self.kill();
+        for _ in 0..50 {
+            if fs::remove_dir(&self.cgroup).is_ok() || !self.cgroup.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Self::remove_dir(&self.work_dir);
+    }
+
+    /// Removes a leftover job cgroup or working directory, killing anything still in a cgroup.
+    fn remove_dir(path: &Path) {
+        let _ = fs::write(path.join("cgroup.kill"), "1");
+        let removed = match path.join("cgroup.procs").exists() {
+            true => fs::remove_dir(path),
+            false => fs::remove_dir_all(path),
+        };
+        if let Err(e) = removed
+            && path.exists()
+        {
+            tracing::error!("Failed to remove {path:?}: {e}");
+        }
+    }
+}
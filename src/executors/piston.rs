@@ -0,0 +1,468 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ExecutionRequest, ExecutionResult, ExecutionStatus, Executor, ResourceUsage, SourceFile,
+    parse_size,
+};
+use crate::{
+    config::{BotConfig, InputConfig, LanguageOverrides, PistonConfig},
+    runners::LANGUAGES,
+};
+
+/// Extra time allowed on top of both stages' timeouts for the request itself.
+const REQUEST_SLACK: Duration = Duration::from_secs(30);
+
+/// Runs jobs on a Piston instance through its `/api/v2/execute` endpoint, for communities that
+/// already host one. Piston runs the first file it is given, compiling every file first for
+/// compiled languages, and enforces the language's time and memory limits itself. Other
+/// sandboxing settings are up to the Piston instance, and programs can't send files back.
+pub struct PistonExecutor {
+    pub input: InputConfig,
+    pub languages: LanguageOverrides,
+    pub piston: PistonConfig,
+    client: Client,
+}
+
+#[derive(Serialize)]
+struct PistonRequest<'a> {
+    language: &'a str,
+    version: &'a str,
+    files: Vec<PistonFile<'a>>,
+    stdin: &'a str,
+    compile_timeout: u64,
+    run_timeout: u64,
+    compile_memory_limit: i64,
+    run_memory_limit: i64,
+}
+
+#[derive(Serialize)]
+struct PistonFile<'a> {
+    name: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct PistonResponse {
+    run: Option<Stage>,
+    compile: Option<Stage>,
+}
+
+/// How the compile or run stage ended. Only the older fields are always present, the rest are
+/// reported by newer versions of Piston.
+#[derive(Deserialize)]
+struct Stage {
+    stdout: String,
+    stderr: String,
+    code: Option<i32>,
+    signal: Option<String>,
+    /// `TO` when the stage timed out, `OL` or `EL` when it printed too much, and `SG` when it
+    /// was killed by a signal.
+    status: Option<String>,
+    /// Milliseconds.
+    cpu_time: Option<u64>,
+    /// Milliseconds.
+    wall_time: Option<u64>,
+    /// Bytes.
+    memory: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct PistonError {
+    message: String,
+}
+
+impl PistonExecutor {
+    pub fn from_config(config: &BotConfig) -> Self {
+        Self {
+            input: config.input.clone(),
+            languages: config.languages.clone(),
+            piston: config.piston.clone(),
+            client: Client::new(),
+        }
+    }
+
+    /// Name Piston knows the language by, which is usually our own.
+    fn piston_language<'a>(&'a self, language: &'a str) -> &'a str {
+        match self.piston.languages.get(language) {
+            Some(name) => name,
+            None => match language {
+                "cpp" => "c++",
+                language => language,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for PistonExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        let ExecutionRequest {
+            language,
+            files,
+            stdin,
+        } = request;
+        let language = language.as_str();
+
+        // Validate input
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+        request.validate(&self.input)?;
+
+        let config = LANGUAGES
+            .get(language)
+            .ok_or_else(|| format!("Unsupported language: {language}"))?;
+        let security = self.languages.security_config(config.as_ref());
+
+        // Piston runs the first file, so a project's entry point goes first
+        let source_file = config.source_file();
+        let mut files = match files.as_slice() {
+            [file] => vec![PistonFile {
+                name: &source_file,
+                content: &file.contents,
+            }],
+            files => files
+                .iter()
+                .map(|file| {
+                    SourceFile::validate_name(&file.name)?;
+                    Ok(PistonFile {
+                        name: &file.name,
+                        content: &file.contents,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+        };
+        let entry = files
+            .iter()
+            .position(|file| {
+                file.name
+                    .ends_with(&format!(".{}", config.file_extension()))
+            })
+            .ok_or_else(|| format!("No .{} file found", config.file_extension()))?;
+        files.swap(0, entry);
+
+        // Piston refuses limits above its own, so the language's are capped at the instance's
+        let mut memory_limit = parse_size(&security.memory_limit)
+            .ok_or_else(|| format!("Invalid memory limit: {}", security.memory_limit))?;
+        if let Some(max) = &self.piston.max_memory_limit {
+            let max = parse_size(max).ok_or_else(|| format!("Invalid memory limit: {max}"))?;
+            memory_limit = memory_limit.min(max);
+        }
+        let timeout = Duration::from_secs(security.timeout_duration);
+        let compile_timeout = timeout.min(Duration::from_millis(self.piston.max_compile_timeout));
+        let run_timeout = timeout.min(Duration::from_millis(self.piston.max_run_timeout));
+        let body = PistonRequest {
+            language: self.piston_language(language),
+            version: "*",
+            files,
+            stdin,
+            compile_timeout: compile_timeout.as_millis() as u64,
+            run_timeout: run_timeout.as_millis() as u64,
+            compile_memory_limit: memory_limit as i64,
+            run_memory_limit: memory_limit as i64,
+        };
+
+        tracing::info!("Executing on Piston for language: {language}");
+
+        let started_at = Instant::now();
+        let mut piston_request = self
+            .client
+            .post(format!(
+                "{}/api/v2/execute",
+                self.piston.url.trim_end_matches('/')
+            ))
+            .timeout(compile_timeout + run_timeout + REQUEST_SLACK)
+            .json(&body);
+        if let Some(api_key) = &self.piston.api_key {
+            piston_request = piston_request.header("Authorization", api_key);
+        }
+
+        let response = piston_request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Piston: {e}"))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response
+                .json::<PistonError>()
+                .await
+                .map(|error| error.message)
+                .unwrap_or_else(|_| status.to_string());
+            return Err(format!("Piston refused the job: {message}"));
+        }
+
+        let response = response
+            .json::<PistonResponse>()
+            .await
+            .map_err(|e| format!("Invalid response from Piston: {e}"))?;
+        let elapsed = started_at.elapsed();
+
+        // A failed build is reported like a failed run, with the compiler's output
+        let stage = match (response.compile, response.run) {
+            (Some(compile), _) if compile.code != Some(0) => compile,
+            (compile, Some(mut run)) => {
+                if let Some(compile) = compile {
+                    run.stdout.insert_str(0, &compile.stdout);
+                    run.stderr.insert_str(0, &compile.stderr);
+                }
+                run
+            }
+            (Some(compile), None) => compile,
+            (None, None) => return Err("Invalid response from Piston: no run stage".into()),
+        };
+
+        let status = match (stage.status.as_deref(), stage.signal.as_deref()) {
+            (Some("TO"), _) => ExecutionStatus::TimedOut,
+            (Some("OL" | "EL"), _) => ExecutionStatus::OutputLimitExceeded,
+            (_, Some(signal)) => ExecutionStatus::Signaled(signal_number(signal)),
+            _ => ExecutionStatus::Completed,
+        };
+
+        Ok(ExecutionResult {
+            stdout: stage.stdout,
+            stderr: stage.stderr,
+            exit_code: match status {
+                ExecutionStatus::TimedOut => Some(124),
+                _ => stage.code,
+            },
+            status,
+            files: Vec::new(),
+            usage: ResourceUsage {
+                wall_time: stage.wall_time.map_or(elapsed, Duration::from_millis),
+                cpu_time: stage.cpu_time.map(Duration::from_millis),
+                peak_memory: stage.memory,
+            },
+        })
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        LANGUAGES.keys().copied().collect()
+    }
+}
+
+/// Number of a signal Piston reports by name, such as `SIGSEGV`.
+fn signal_number(name: &str) -> i32 {
+    let signals = HashMap::from([
+        ("SIGHUP", 1),
+        ("SIGINT", 2),
+        ("SIGQUIT", 3),
+        ("SIGILL", 4),
+        ("SIGTRAP", 5),
+        ("SIGABRT", 6),
+        ("SIGBUS", 7),
+        ("SIGFPE", 8),
+        ("SIGKILL", 9),
+        ("SIGSEGV", 11),
+        ("SIGPIPE", 13),
+        ("SIGALRM", 14),
+        ("SIGTERM", 15),
+        ("SIGXCPU", 24),
+        ("SIGXFSZ", 25),
+    ]);
+
+    signals.get(name).copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Serves `/api/v2/execute` on a free local port, answering every job with `response` and
+    /// keeping the bodies it was sent.
+    fn spawn_piston(status: StatusCode, response: Value) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/api/v2/execute",
+                post(
+                    move |State(bodies): State<Arc<Mutex<Vec<Value>>>>, Json(body): Json<Value>| {
+                        bodies.lock().unwrap().push(body);
+                        async move { (status, Json(response)) }
+                    },
+                ),
+            )
+            .with_state(bodies.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        (format!("http://{address}"), bodies)
+    }
+
+    fn executor(url: String, config: &str) -> PistonExecutor {
+        let mut config = toml::from_str::<BotConfig>(config).unwrap();
+        config.piston.url = url;
+        PistonExecutor::from_config(&config)
+    }
+
+    fn request(language: &str, files: &[(&str, &str)]) -> ExecutionRequest {
+        ExecutionRequest {
+            language: language.into(),
+            files: files
+                .iter()
+                .map(|(name, contents)| SourceFile {
+                    name: (*name).into(),
+                    contents: (*contents).into(),
+                })
+                .collect(),
+            stdin: "input".into(),
+        }
+    }
+
+    fn stage(stdout: &str, code: i32, status: Option<&str>) -> Value {
+        json!({
+            "stdout": stdout,
+            "stderr": "",
+            "code": code,
+            "signal": null,
+            "status": status,
+            "cpu_time": 12,
+            "wall_time": 34,
+            "memory": 1024,
+        })
+    }
+
+    #[tokio::test]
+    async fn caps_limits_at_the_instance_maximums() {
+        let (url, bodies) = spawn_piston(StatusCode::OK, json!({ "run": stage("hi\n", 0, None) }));
+        let config = r#"
+            [languages.python]
+            timeout_duration = 300
+            memory_limit = "1g"
+
+            [piston]
+            max_memory_limit = "256m"
+        "#;
+        let executor = executor(url, config);
+
+        let result = executor
+            .execute(&request("python", &[("main.py", "print('hi')")]))
+            .await
+            .unwrap();
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.stdout, "hi\n");
+        assert_eq!(result.usage.wall_time, Duration::from_millis(34));
+        assert_eq!(result.usage.cpu_time, Some(Duration::from_millis(12)));
+        assert_eq!(result.usage.peak_memory, Some(1024));
+
+        let body = bodies.lock().unwrap().pop().unwrap();
+        assert_eq!(body["language"], "python");
+        assert_eq!(body["version"], "*");
+        assert_eq!(body["stdin"], "input");
+        assert_eq!(body["files"][0]["name"], "main.py");
+        assert_eq!(body["compile_timeout"], 10000);
+        assert_eq!(body["run_timeout"], 3000);
+        assert_eq!(body["compile_memory_limit"], 256 << 20);
+        assert_eq!(body["run_memory_limit"], 256 << 20);
+    }
+
+    #[tokio::test]
+    async fn keeps_limits_below_the_maximums() {
+        let (url, bodies) = spawn_piston(StatusCode::OK, json!({ "run": stage("", 0, None) }));
+        let config = r#"
+            [languages.python]
+            timeout_duration = 2
+            memory_limit = "64m"
+        "#;
+        let executor = executor(url, config);
+
+        executor
+            .execute(&request("python", &[("main.py", "pass")]))
+            .await
+            .unwrap();
+
+        let body = bodies.lock().unwrap().pop().unwrap();
+        assert_eq!(body["compile_timeout"], 2000);
+        assert_eq!(body["run_timeout"], 2000);
+        assert_eq!(body["run_memory_limit"], 64 << 20);
+    }
+
+    #[tokio::test]
+    async fn sends_the_entry_point_first() {
+        let (url, bodies) = spawn_piston(StatusCode::OK, json!({ "run": stage("", 0, None) }));
+        let executor = executor(url, "");
+
+        let files = [("util.h", "int f();"), ("main.cpp", "int main() {}")];
+        executor.execute(&request("cpp", &files)).await.unwrap();
+
+        let body = bodies.lock().unwrap().pop().unwrap();
+        assert_eq!(body["language"], "c++");
+        assert_eq!(body["files"][0]["name"], "main.cpp");
+        assert_eq!(body["files"][1]["name"], "util.h");
+    }
+
+    #[tokio::test]
+    async fn reports_failed_builds_and_timeouts() {
+        let response = json!({ "compile": stage("error: expected ';'", 1, None) });
+        let (url, _) = spawn_piston(StatusCode::OK, response);
+        let result = executor(url, "")
+            .execute(&request("cpp", &[("main.cpp", "int main() {")]))
+            .await
+            .unwrap();
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.exit_code, Some(1));
+        assert_eq!(result.stdout, "error: expected ';'");
+
+        let (url, _) = spawn_piston(StatusCode::OK, json!({ "run": stage("", 1, Some("TO")) }));
+        let result = executor(url, "")
+            .execute(&request("python", &[("main.py", "while True: pass")]))
+            .await
+            .unwrap();
+        assert_eq!(result.status, ExecutionStatus::TimedOut);
+        assert_eq!(result.exit_code, Some(124));
+    }
+
+    #[tokio::test]
+    async fn reports_refused_jobs() {
+        let response =
+            json!({ "message": "run_timeout cannot exceed the configured limit of 3000" });
+        let (url, _) = spawn_piston(StatusCode::BAD_REQUEST, response);
+
+        let error = executor(url, "")
+            .execute(&request("python", &[("main.py", "pass")]))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            "Piston refused the job: run_timeout cannot exceed the configured limit of 3000"
+        );
+    }
+}
@@ -0,0 +1,377 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    env,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use super::{ExecutionRequest, ExecutionResult, Executor};
+use crate::{
+    config::{BotConfig, InputConfig, RemoteConfig},
+    runners::LANGUAGES,
+    worker::TOKEN_VAR,
+};
+
+/// How long a worker has to answer a health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs jobs on worker processes started with `compiler-bot worker`, usually on other machines,
+/// so the bot itself never runs untrusted code.
+///
+/// Each job goes to the healthy worker with the fewest jobs in flight. Workers are health checked
+/// periodically, and one that can't be reached is skipped until it passes a check again.
+pub struct RemoteExecutor {
+    pub input: InputConfig,
+    workers: Arc<Vec<Worker>>,
+    client: Client,
+    token: String,
+    /// Rotates which of equally busy workers is picked first.
+    next: AtomicUsize,
+}
+
+struct Worker {
+    url: String,
+    healthy: AtomicBool,
+    running: AtomicUsize,
+}
+
+/// Counts a job as running on a worker until it is dropped, even if the job is given up on.
+struct Running<'a>(&'a AtomicUsize);
+
+impl<'a> Running<'a> {
+    fn start(running: &'a AtomicUsize) -> Self {
+        running.fetch_add(1, Ordering::Relaxed);
+        Self(running)
+    }
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Body of a worker's answer to a job it couldn't run.
+#[derive(Deserialize)]
+struct JobError {
+    error: String,
+}
+
+enum JobFailure {
+    /// The job never reached the worker, so it can be tried on another.
+    Unreachable(String),
+    Failed(String),
+}
+
+impl RemoteExecutor {
+    /// Creates the executor for the configured workers, starting their health checks.
+    pub fn from_config(config: &BotConfig) -> Self {
+        let executor = Self::with_config(config);
+        executor.spawn_health_checks(config.remote.health_check_interval);
+        executor
+    }
+
+    /// Creates the executor for the configured workers without checking their health, so every
+    /// worker is tried until a job fails to reach it.
+    pub fn with_config(config: &BotConfig) -> Self {
+        Self::new(config, env::var(TOKEN_VAR).unwrap_or_default())
+    }
+
+    fn new(config: &BotConfig, token: String) -> Self {
+        let RemoteConfig {
+            workers,
+            request_timeout,
+            ..
+        } = &config.remote;
+
+        if token.is_empty() {
+            tracing::warn!("{TOKEN_VAR} is not set, workers will refuse every job");
+        }
+        if workers.is_empty() {
+            tracing::warn!("No workers are configured for the remote backend");
+        }
+
+        let workers = Arc::new(
+            workers
+                .iter()
+                .map(|url| Worker {
+                    url: url.trim_end_matches('/').to_string(),
+                    // Assume workers are up until a check says otherwise
+                    healthy: AtomicBool::new(true),
+                    running: AtomicUsize::new(0),
+                })
+                .collect::<Vec<_>>(),
+        );
+        let client = Client::builder()
+            .timeout(Duration::from_secs(*request_timeout))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            input: config.input.clone(),
+            workers,
+            client,
+            token,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn spawn_health_checks(&self, interval: u64) {
+        tokio::spawn({
+            let (workers, client, token) = (
+                self.workers.clone(),
+                self.client.clone(),
+                self.token.clone(),
+            );
+            let interval = Duration::from_secs(interval.max(1));
+            async move {
+                let mut interval = tokio::time::interval(interval);
+                loop {
+                    interval.tick().await;
+                    for worker in workers.iter() {
+                        worker.check_health(&client, &token).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Orders the workers by how busy they are, healthy ones first.
+    fn candidates(&self) -> Vec<&Worker> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut workers = (0..self.workers.len())
+            .map(|i| &self.workers[(start + i) % self.workers.len()])
+            .collect::<Vec<_>>();
+        workers.sort_by_key(|worker| {
+            (
+                !worker.healthy.load(Ordering::Relaxed),
+                worker.running.load(Ordering::Relaxed),
+            )
+        });
+
+        workers
+    }
+}
+
+impl Worker {
+    async fn check_health(&self, client: &Client, token: &str) {
+        let response = client
+            .get(format!("{}/health", self.url))
+            .bearer_auth(token)
+            .timeout(HEALTH_CHECK_TIMEOUT)
+            .send()
+            .await;
+
+        let healthy = match response {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                tracing::warn!(
+                    "Worker {} failed its health check: {}",
+                    self.url,
+                    response.status()
+                );
+                false
+            }
+            Err(e) => {
+                tracing::warn!("Worker {} failed its health check: {e}", self.url);
+                false
+            }
+        };
+
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            tracing::info!(
+                "Worker {} is now {}",
+                self.url,
+                if healthy { "healthy" } else { "unhealthy" }
+            );
+        }
+    }
+
+    /// Runs the job on this worker, telling apart jobs that never reached it.
+    async fn execute(
+        &self,
+        client: &Client,
+        token: &str,
+        request: &ExecutionRequest,
+    ) -> Result<ExecutionResult, JobFailure> {
+        let _running = Running::start(&self.running);
+        let response = client
+            .post(format!("{}/jobs", self.url))
+            .bearer_auth(token)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| match e.is_connect() {
+                true => JobFailure::Unreachable(e.to_string()),
+                false => JobFailure::Failed(format!("Job failed on worker: {e}")),
+            })?;
+
+        match response.status() {
+            status if status.is_success() => response
+                .json()
+                .await
+                .map_err(|e| JobFailure::Failed(format!("Invalid response from worker: {e}"))),
+            // The worker refused the job or its sandbox failed, which the user is told about
+            StatusCode::UNPROCESSABLE_ENTITY => Err(JobFailure::Failed(
+                response
+                    .json::<JobError>()
+                    .await
+                    .map(|error| error.error)
+                    .unwrap_or_else(|e| format!("Invalid response from worker: {e}")),
+            )),
+            status => Err(JobFailure::Failed(format!("Worker returned {status}"))),
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for RemoteExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        // Validate input before it is sent anywhere
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+        request.validate(&self.input)?;
+
+        for worker in self.candidates() {
+            match worker.execute(&self.client, &self.token, request).await {
+                Ok(result) => return Ok(result),
+                // Only jobs that never reached a worker are retried on another, so nothing runs
+                // twice
+                Err(JobFailure::Unreachable(e)) => {
+                    tracing::warn!("Worker {} is unreachable: {e}", worker.url);
+                    worker.healthy.store(false, Ordering::Relaxed);
+                }
+                Err(JobFailure::Failed(e)) => {
+                    tracing::error!("Job failed on worker {}: {e}", worker.url);
+                    return Err(e);
+                }
+            }
+        }
+
+        Err("No workers are available to run code".into())
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        // Workers run the same build of the bot, so they support the same languages
+        LANGUAGES.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        executors::{ExecutionStatus, MockExecutor, SourceFile},
+        worker,
+    };
+
+    const TOKEN: &str = "secret";
+
+    /// Serves the worker's job API on a free local port, running jobs with the mock backend.
+    fn spawn_worker(token: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = worker::app(
+            &BotConfig::default(),
+            Arc::new(MockExecutor::new()),
+            token.into(),
+        );
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        format!("http://{address}")
+    }
+
+    fn executor(workers: Vec<String>) -> RemoteExecutor {
+        let mut config = BotConfig::default();
+        config.remote.workers = workers;
+        RemoteExecutor::new(&config, TOKEN.into())
+    }
+
+    fn request() -> ExecutionRequest {
+        ExecutionRequest {
+            language: "python".into(),
+            files: vec![SourceFile {
+                name: "main.py".into(),
+                contents: "print(input())".into(),
+            }],
+            stdin: "hello\n".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_jobs_on_a_worker() {
+        let executor = executor(vec![spawn_worker(TOKEN)]);
+
+        let result = executor.execute(&request()).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(result.stdout, "hello\nmain.py (14 bytes)\n");
+        assert_eq!(executor.workers[0].running.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn skips_unreachable_workers() {
+        // Nothing listens on a port once its listener is closed
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let executor = executor(vec![format!("http://{closed}"), spawn_worker(TOKEN)]);
+
+        let result = executor.execute(&request()).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert!(!executor.workers[0].healthy.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn reports_refused_tokens() {
+        let executor = executor(vec![spawn_worker("other")]);
+
+        let error = executor.execute(&request()).await.unwrap_err();
+        assert_eq!(error, "Worker returned 401 Unauthorized");
+    }
+
+    #[tokio::test]
+    async fn abandoned_jobs_stop_counting_as_running() {
+        // The connection is accepted by the kernel, but the job is never answered
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let executor = executor(vec![format!("http://{}", listener.local_addr().unwrap())]);
+
+        let request = request();
+        let job = executor.execute(&request);
+        let timed_out = tokio::time::timeout(Duration::from_millis(200), job).await;
+        assert!(timed_out.is_err());
+        assert_eq!(executor.workers[0].running.load(Ordering::Relaxed), 0);
+    }
+}
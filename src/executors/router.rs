@@ -0,0 +1,203 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, LazyLock},
+};
+
+use async_trait::async_trait;
+use regex::Regex;
+
+use super::{ExecutionRequest, ExecutionResult, Executor, WasiExecutor};
+
+/// Top-level modules of the standard library that work the same in the WASI interpreter.
+/// Snippets importing anything else may need native packages or the OS, and run in the backend.
+const WASI_MODULES: &[&str] = &[
+    "abc",
+    "array",
+    "base64",
+    "binascii",
+    "bisect",
+    "calendar",
+    "cmath",
+    "collections",
+    "contextlib",
+    "copy",
+    "csv",
+    "dataclasses",
+    "datetime",
+    "decimal",
+    "difflib",
+    "enum",
+    "fractions",
+    "functools",
+    "hashlib",
+    "heapq",
+    "io",
+    "itertools",
+    "json",
+    "math",
+    "numbers",
+    "operator",
+    "pprint",
+    "random",
+    "re",
+    "statistics",
+    "string",
+    "struct",
+    "sys",
+    "textwrap",
+    "time",
+    "typing",
+    "unicodedata",
+];
+
+/// Matches import statements, at the start of a line or after a `;`, capturing the module of a
+/// `from` import or the list of modules of a plain one.
+static IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)(?:^|;)[ \t]*(?:from[ \t]+(\S+)[ \t]+import\b|import[ \t]+([^#;\n]+))")
+        .unwrap()
+});
+
+/// Sends plain Python snippets to the embedded WASI interpreter, which starts much faster than a
+/// container, and everything else to the configured backend. Snippets also go to the backend
+/// while too many interpreter runs are stuck past their timeout.
+pub struct Router {
+    backend: Arc<dyn Executor>,
+    wasi: WasiExecutor,
+}
+
+impl Router {
+    pub fn new(backend: Arc<dyn Executor>, wasi: WasiExecutor) -> Self {
+        Self { backend, wasi }
+    }
+
+    /// Whether the request is a single Python file that only imports modules known to work in
+    /// the interpreter.
+    fn is_plain_python(request: &ExecutionRequest) -> bool {
+        let [file] = request.files.as_slice() else {
+            return false;
+        };
+        if request.language != "python" || file.contents.contains("__import__") {
+            return false;
+        }
+
+        let allowed = WASI_MODULES.iter().copied().collect::<HashSet<_>>();
+        IMPORT.captures_iter(&file.contents).all(|captures| {
+            let modules = match (captures.get(1), captures.get(2)) {
+                (Some(module), _) => vec![module.as_str()],
+                (_, Some(modules)) => modules
+                    .as_str()
+                    .split(',')
+                    .filter_map(|module| module.split_whitespace().next())
+                    .collect(),
+                _ => return false,
+            };
+
+            modules.into_iter().all(|module| {
+                let top_level = module.split('.').next().unwrap_or_default();
+                allowed.contains(top_level)
+            })
+        })
+    }
+}
+
+#[async_trait]
+impl Executor for Router {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        match Self::is_plain_python(request) && !self.wasi.is_saturated() {
+            true => self.wasi.execute(request).await,
+            false => self.backend.execute(request).await,
+        }
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        self.backend.supported_languages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::SourceFile;
+
+    fn request(language: &str, files: &[&str]) -> ExecutionRequest {
+        ExecutionRequest {
+            language: language.into(),
+            files: files
+                .iter()
+                .enumerate()
+                .map(|(i, contents)| SourceFile {
+                    name: format!("file{i}.py"),
+                    contents: (*contents).into(),
+                })
+                .collect(),
+            stdin: String::new(),
+        }
+    }
+
+    fn is_plain(code: &str) -> bool {
+        Router::is_plain_python(&request("python", &[code]))
+    }
+
+    #[test]
+    fn plain_imports_of_known_modules_stay_plain() {
+        assert!(is_plain("print(sum(range(10)))"));
+        assert!(is_plain("import math\nprint(math.pi)"));
+        assert!(is_plain("import json as j, collections.abc  # comment"));
+        assert!(is_plain(
+            "def f():\n    import itertools\n    return itertools"
+        ));
+        // Only statements count, not words in the code
+        assert!(is_plain("print('import os')"));
+    }
+
+    #[test]
+    fn other_modules_go_to_the_backend() {
+        assert!(!is_plain("import os"));
+        assert!(!is_plain("import math, numpy as np"));
+        assert!(!is_plain("import xml.etree.ElementTree"));
+        assert!(!is_plain("def f():\n    import subprocess"));
+        assert!(!is_plain("import math; import socket"));
+    }
+
+    #[test]
+    fn from_imports_are_checked_by_their_module() {
+        assert!(is_plain("from collections import deque"));
+        assert!(is_plain("from collections.abc import Mapping"));
+        assert!(!is_plain("from os.path import join"));
+        assert!(!is_plain("from . import sibling"));
+    }
+
+    #[test]
+    fn dynamic_imports_go_to_the_backend() {
+        assert!(!is_plain("os = __import__('os')"));
+    }
+
+    #[test]
+    fn only_single_python_files_are_plain() {
+        let files = ["print(1)", "print(2)"];
+        assert!(!Router::is_plain_python(&request("python", &files)));
+        assert!(!Router::is_plain_python(&request(
+            "cpp",
+            &["int main() {}"]
+        )));
+        assert!(!Router::is_plain_python(&request("python", &[])));
+    }
+}
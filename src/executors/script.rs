@@ -0,0 +1,117 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::path::Path;
+
+use super::SourceFile;
+use crate::runners::{Language, SUPPORT_DIR};
+
+/// Directory inside the sandbox that submitted files are written to and run from.
+pub(super) const WORKDIR: &str = "/sandbox";
+
+/// Directory inside the sandbox whose files are sent back to the user after the run.
+pub(super) const OUTPUT_DIR: &str = "/out";
+
+/// Shell prelude that writes the submitted files from the head of stdin into the working
+/// directory, leaving the rest of stdin for the program. Each file is sent as its relative path
+/// and byte length on separate lines followed by its contents, and an empty line ends the list.
+const UNPACK_FILES: &str = r#"while IFS= read -r name && [ -n "$name" ]; do IFS= read -r size; mkdir -p "$(dirname "$name")"; head -c "$size" > "$name"; done"#;
+
+/// Runs a command in its own process group and waits for it to finish, leaving its exit status
+/// in `$status`. Bash runs as PID 1, which ignores SIGTERM unless it has a handler, so the
+/// handler forwards it to everything the command started.
+fn run_in_process_group(command: &str) -> String {
+    format!(
+        r#"set -m; trap 'kill -TERM -- -$pid 2>/dev/null' TERM; {{ {command}; }} & pid=$!; set +m; while wait $pid; status=$?; kill -0 $pid 2>/dev/null; do :; done"#
+    )
+}
+
+/// Shell epilogue that reports the run's wall time in nanoseconds along with the sandbox's
+/// CPU time in microseconds and peak memory in bytes from its cgroup. The report is written to
+/// stderr after the marker read from stdin, which the program never sees, so it can't be forged.
+const REPORT_USAGE: &str = r#"end=$(date +%s%N); printf '\n%s %s %s %s\n' "$marker" "$((end - start))" "$(sed -n 's/^usage_usec //p' /sys/fs/cgroup/cpu.stat 2>/dev/null)" "$(cat /sys/fs/cgroup/memory.peak 2>/dev/null)" >&2; exit $status"#;
+
+/// Works out the files to write and the command that runs them. A single file is saved under the
+/// language's usual source file name, while several files are built as a project whose entry
+/// point is the first file with the language's extension.
+pub(super) fn prepare_files(
+    files: &[SourceFile],
+    config: &(dyn Language + Send + Sync),
+) -> Result<(Vec<SourceFile>, String), String> {
+    match files {
+        [file] => {
+            let file = SourceFile {
+                name: config.source_file(),
+                contents: file.contents.clone(),
+            };
+            Ok((vec![file], config.command().to_string()))
+        }
+        _ => {
+            for file in files {
+                SourceFile::validate_name(&file.name)?;
+            }
+
+            let entry = files
+                .iter()
+                .find(|file| {
+                    Path::new(&file.name)
+                        .extension()
+                        .is_some_and(|ext| ext == config.file_extension())
+                })
+                .ok_or_else(|| format!("No .{} file found", config.file_extension()))?;
+
+            let command = format!("ENTRY='{}'; {}", entry.name, config.project_command());
+            Ok((files.to_vec(), command))
+        }
+    }
+}
+
+/// Builds the script that unpacks the files before running the command and reports the
+/// resources it used afterwards.
+pub(super) fn run_script(command: &str) -> String {
+    format!(
+        "IFS= read -r marker; mkdir -p {OUTPUT_DIR}; {UNPACK_FILES}; \
+         start=$(date +%s%N); {}; {REPORT_USAGE}",
+        run_in_process_group(command)
+    )
+}
+
+/// Builds the head of the script's stdin: the marker followed by the language's support files
+/// and the source files. The program's own input goes after it.
+pub(super) fn payload(
+    marker: &str,
+    config: &(dyn Language + Send + Sync),
+    files: &[SourceFile],
+) -> Vec<u8> {
+    let support_files = config
+        .support_files()
+        .iter()
+        .map(|(name, contents)| (format!("{SUPPORT_DIR}/{name}"), contents.as_bytes()));
+    let source_files = files
+        .iter()
+        .map(|file| (file.name.clone(), file.contents.as_bytes()));
+
+    let mut payload = format!("{marker}\n").into_bytes();
+    for (name, contents) in support_files.chain(source_files) {
+        payload.extend(format!("{name}\n{}\n", contents.len()).as_bytes());
+        payload.extend(contents);
+    }
+    payload.push(b'\n');
+
+    payload
+}
@@ -0,0 +1,618 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use uuid::Uuid;
+use wasmtime::{
+    Config, Engine, Linker, Module, ResourceLimiter, Store, StoreLimits, StoreLimitsBuilder, Trap,
+};
+use wasmtime_wasi::{
+    DirPerms, FilePerms, I32Exit, WasiCtxBuilder,
+    pipe::{MemoryInputPipe, MemoryOutputPipe},
+    preview1::{self, WasiP1Ctx},
+};
+
+use super::{
+    ExecutionRequest, ExecutionResult, ExecutionStatus, Executor, ResourceUsage, SourceFile,
+    capture, parse_size,
+    script::{OUTPUT_DIR, WORKDIR},
+};
+use crate::{
+    config::{BotConfig, InputConfig, LanguageOverrides, OutputConfig, SecurityConfig, WasiConfig},
+    runners::LANGUAGES,
+};
+
+/// The only language the interpreter runs.
+const LANGUAGE: &str = "python";
+
+/// Where the interpreter looks for its standard library when it isn't embedded in the module.
+const PYTHON_LIB_DIR: &str = "/usr/local/lib";
+
+/// How often the engine's epoch advances, which is how precisely the time limit is enforced.
+const EPOCH_INTERVAL: Duration = Duration::from_millis(100);
+/// Runs given up on while still blocked in a host call, past which snippets go to the backend
+/// instead so they can't use up the blocking thread pool.
+const MAX_STUCK_RUNS: usize = 8;
+
+/// Runs Python in a WASI build of CPython on a WebAssembly runtime embedded in the bot, which
+/// starts far faster than a container for quick snippets.
+///
+/// The program only sees its working and output directories, and has no network access or
+/// subprocesses. The language's memory limit caps the interpreter's linear memory, and its
+/// timeout is enforced by interrupting the interpreter, along with an optional fuel budget.
+pub struct WasiExecutor {
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub languages: LanguageOverrides,
+    interpreter: Arc<Interpreter>,
+}
+
+/// The compiled interpreter, shared with the threads it runs on.
+struct Interpreter {
+    wasi: WasiConfig,
+    engine: Engine,
+    module: Module,
+    linker: Linker<State>,
+    /// Runs past their deadline whose threads haven't returned yet.
+    stuck: AtomicUsize,
+}
+
+struct State {
+    wasi: WasiP1Ctx,
+    limits: Limits,
+}
+
+/// The store's limits, noting when memory wasn't allowed to grow past them.
+struct Limits {
+    limits: StoreLimits,
+    out_of_memory: bool,
+}
+
+impl ResourceLimiter for Limits {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        let growing = self.limits.memory_growing(current, desired, maximum);
+        self.out_of_memory |= !matches!(growing, Ok(true));
+        growing
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+/// How the interpreter run ended, before its output is collected.
+struct Run {
+    exit: Result<(), wasmtime::Error>,
+    wall_time: Duration,
+    /// Size the interpreter's linear memory grew to, which never shrinks.
+    peak_memory: Option<u64>,
+    /// Memory wasn't allowed to grow past the language's limit.
+    out_of_memory: bool,
+}
+
+impl WasiExecutor {
+    /// Compiles the interpreter module, which can take a few seconds for CPython.
+    pub fn from_config(config: &BotConfig, module: &str) -> Result<Self, String> {
+        let mut engine_config = Config::new();
+        engine_config.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&engine_config)
+            .map_err(|e| format!("Failed to create WebAssembly engine: {e}"))?;
+
+        let started_at = Instant::now();
+        let module = Module::from_file(&engine, module)
+            .map_err(|e| format!("Failed to load WASI module {module}: {e}"))?;
+        tracing::info!("Compiled WASI interpreter in {:?}", started_at.elapsed());
+
+        let mut linker = Linker::new(&engine);
+        preview1::add_to_linker_sync(&mut linker, |state: &mut State| &mut state.wasi)
+            .map_err(|e| format!("Failed to link WASI: {e}"))?;
+
+        // Count time in epochs for as long as the bot runs
+        std::thread::spawn({
+            let engine = engine.clone();
+            move || {
+                loop {
+                    std::thread::sleep(EPOCH_INTERVAL);
+                    engine.increment_epoch();
+                }
+            }
+        });
+
+        Ok(Self {
+            input: config.input.clone(),
+            output: config.output.clone(),
+            languages: config.languages.clone(),
+            interpreter: Arc::new(Interpreter {
+                wasi: config.wasi.clone(),
+                engine,
+                module,
+                linker,
+                stuck: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    /// Whether too many earlier runs are still stuck in the interpreter to start another.
+    pub fn is_saturated(&self) -> bool {
+        self.interpreter.stuck.load(Ordering::Relaxed) >= MAX_STUCK_RUNS
+    }
+
+    /// Turns the run into a result the same way the sandboxes report theirs.
+    fn build_result(
+        &self,
+        run: Run,
+        stdout: MemoryOutputPipe,
+        stderr: MemoryOutputPipe,
+        security: &SecurityConfig,
+    ) -> ExecutionResult {
+        let limit_exceeded = [&stdout, &stderr]
+            .iter()
+            .any(|pipe| pipe.contents().len() >= self.output.max_capture_size);
+        let mut stderr = String::from_utf8_lossy(&stderr.contents()).into_owned();
+
+        let (status, exit_code) = match &run.exit {
+            _ if limit_exceeded => (ExecutionStatus::OutputLimitExceeded, None),
+            Ok(()) => (ExecutionStatus::Completed, Some(0)),
+            Err(e) => match (e.downcast_ref::<I32Exit>(), e.downcast_ref::<Trap>()) {
+                (Some(exit), _) => (ExecutionStatus::Completed, Some(exit.0)),
+                (_, Some(Trap::OutOfFuel | Trap::Interrupt)) => {
+                    (ExecutionStatus::TimedOut, Some(124))
+                }
+                // Growing memory past the limit traps
+                _ if run.out_of_memory => {
+                    let status = ExecutionStatus::OutOfMemory {
+                        memory_limit: security.memory_limit.clone(),
+                    };
+                    (status, None)
+                }
+                _ => {
+                    stderr.push_str(&format!("\nInterpreter crashed: {e}"));
+                    (ExecutionStatus::Completed, Some(1))
+                }
+            },
+        };
+
+        ExecutionResult {
+            stdout: String::from_utf8_lossy(&stdout.contents()).into(),
+            stderr,
+            exit_code,
+            status,
+            files: Vec::new(),
+            usage: ResourceUsage {
+                wall_time: run.wall_time,
+                // The thread's CPU time isn't measured, and the wall time includes host calls
+                cpu_time: None,
+                peak_memory: run.peak_memory,
+            },
+        }
+    }
+}
+
+impl Interpreter {
+    /// Runs the interpreter on the entry point to completion, blocking the thread.
+    fn run(
+        &self,
+        job_dir: &Path,
+        entry: &str,
+        stdin: String,
+        stdout: MemoryOutputPipe,
+        stderr: MemoryOutputPipe,
+        security: &SecurityConfig,
+    ) -> Result<Run, String> {
+        let memory_limit = parse_size(&security.memory_limit)
+            .ok_or_else(|| format!("Invalid memory limit: {}", security.memory_limit))?;
+
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.args(&["python", &format!("{WORKDIR}/{entry}")])
+            .stdin(MemoryInputPipe::new(stdin))
+            .stdout(stdout)
+            .stderr(stderr);
+        let preopened = wasi
+            .preopened_dir(
+                job_dir.join("sandbox"),
+                WORKDIR,
+                DirPerms::all(),
+                FilePerms::all(),
+            )
+            .and_then(|wasi| {
+                wasi.preopened_dir(
+                    job_dir.join("out"),
+                    OUTPUT_DIR,
+                    DirPerms::all(),
+                    FilePerms::all(),
+                )
+            })
+            .and_then(|wasi| match &self.wasi.python_lib {
+                Some(lib) => {
+                    wasi.preopened_dir(lib, PYTHON_LIB_DIR, DirPerms::READ, FilePerms::READ)
+                }
+                None => Ok(wasi),
+            });
+        if let Err(e) = preopened {
+            return Err(format!("Failed to set up the sandbox: {e}"));
+        }
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(memory_limit as usize)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(
+            &self.engine,
+            State {
+                wasi: wasi.build_p1(),
+                limits: Limits {
+                    limits,
+                    out_of_memory: false,
+                },
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(match self.wasi.fuel {
+                0 => u64::MAX,
+                fuel => fuel,
+            })
+            .map_err(|e| format!("Failed to set fuel: {e}"))?;
+        let timeout = Duration::from_secs(security.timeout_duration);
+        store.set_epoch_deadline((timeout.as_millis() / EPOCH_INTERVAL.as_millis()) as u64);
+
+        let started_at = Instant::now();
+        let instance = self
+            .linker
+            .instantiate(&mut store, &self.module)
+            .map_err(|e| format!("Failed to start the interpreter: {e}"))?;
+        let start = instance
+            .get_typed_func::<(), ()>(&mut store, "_start")
+            .map_err(|e| format!("Invalid WASI module: {e}"))?;
+
+        let exit = start.call(&mut store, ());
+        let wall_time = started_at.elapsed();
+        let peak_memory = instance
+            .get_memory(&mut store, "memory")
+            .map(|memory| memory.data_size(&store) as u64);
+
+        Ok(Run {
+            exit,
+            wall_time,
+            peak_memory,
+            out_of_memory: store.data().limits.out_of_memory,
+        })
+    }
+}
+
+#[async_trait]
+impl Executor for WasiExecutor {
+    async fn execute(&self, request: &ExecutionRequest) -> Result<ExecutionResult, String> {
+        let ExecutionRequest {
+            language,
+            files,
+            stdin,
+        } = request;
+
+        // Validate input
+        if request.is_empty() {
+            return Ok(ExecutionResult::empty_code());
+        }
+        request.validate(&self.input)?;
+
+        let config = LANGUAGES
+            .get(LANGUAGE)
+            .filter(|_| language == LANGUAGE)
+            .ok_or_else(|| format!("Unsupported language: {language}"))?;
+        let security = self.languages.security_config(config.as_ref());
+
+        // A single file is saved under the language's usual source file name, while a project
+        // is run from its first Python file
+        let files = match files.as_slice() {
+            [file] => vec![SourceFile {
+                name: config.source_file(),
+                contents: file.contents.clone(),
+            }],
+            files => files.to_vec(),
+        };
+        for file in &files {
+            SourceFile::validate_name(&file.name)?;
+        }
+        let entry = files
+            .iter()
+            .find(|file| file.name.ends_with(".py"))
+            .map(|file| file.name.clone())
+            .ok_or("No .py file found")?;
+
+        let job_dir =
+            Path::new(&self.interpreter.wasi.work_dir).join(format!("job-{}", Uuid::new_v4()));
+        let written = write_files(&job_dir, &files);
+        if let Err(e) = written {
+            let _ = fs::remove_dir_all(&job_dir);
+            return Err(format!("Failed to write code to sandbox: {e}"));
+        }
+
+        tracing::info!("Executing WASI interpreter for language: {LANGUAGE}");
+
+        let stdout = MemoryOutputPipe::new(self.output.max_capture_size);
+        let stderr = MemoryOutputPipe::new(self.output.max_capture_size);
+
+        // The interpreter is only interrupted while it runs WebAssembly, so a program blocked
+        // in a host call, such as a long sleep, is given up on rather than waited for
+        let deadline =
+            Duration::from_secs(security.timeout_duration + security.termination_grace_period);
+        let mut handle = tokio::task::spawn_blocking({
+            let interpreter = self.interpreter.clone();
+            let (job_dir, stdin) = (job_dir.clone(), stdin.clone());
+            let (stdout, stderr, security) = (stdout.clone(), stderr.clone(), security.clone());
+            move || interpreter.run(&job_dir, &entry, stdin, stdout, stderr, &security)
+        });
+        let run = tokio::time::timeout(deadline, &mut handle).await;
+
+        let result = match run {
+            Ok(Ok(Ok(run))) => {
+                let mut result = self.build_result(run, stdout, stderr, &security);
+                result.files = capture::read_output_dir(&job_dir.join("out"), &self.output);
+                Ok(result)
+            }
+            Ok(Ok(Err(e))) => Err(e),
+            Ok(Err(e)) => Err(format!("WASI interpreter panicked: {e}")),
+            Err(_) => {
+                tracing::warn!("WASI interpreter ignored its timeout: {job_dir:?}");
+                // The interpreter still has the job's files open, so they are only removed once
+                // its thread returns
+                let interpreter = self.interpreter.clone();
+                interpreter.stuck.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let _ = handle.await;
+                    remove_job_dir(&job_dir);
+                    interpreter.stuck.fetch_sub(1, Ordering::Relaxed);
+                });
+                return Ok(ExecutionResult {
+                    stdout: String::from_utf8_lossy(&stdout.contents()).into(),
+                    stderr: String::from_utf8_lossy(&stderr.contents()).into(),
+                    exit_code: Some(124),
+                    status: ExecutionStatus::TimedOut,
+                    files: Vec::new(),
+                    usage: ResourceUsage {
+                        wall_time: deadline,
+                        ..Default::default()
+                    },
+                });
+            }
+        };
+
+        remove_job_dir(&job_dir);
+
+        result
+    }
+
+    fn supported_languages(&self) -> Vec<&'static str> {
+        vec![LANGUAGE]
+    }
+}
+
+fn remove_job_dir(job_dir: &Path) {
+    if let Err(e) = fs::remove_dir_all(job_dir) {
+        tracing::error!("Failed to remove {job_dir:?}: {e}");
+    }
+}
+
+/// Writes the source files into the job's working directory, and makes its output directory.
+fn write_files(job_dir: &Path, files: &[SourceFile]) -> std::io::Result<()> {
+    let sandbox = job_dir.join("sandbox");
+    fs::create_dir_all(job_dir.join("out"))?;
+
+    for file in files {
+        let path: PathBuf = sandbox.join(&file.name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &file.contents)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// Writes `hello` to stdout, then runs `body`.
+    fn module(body: &str) -> String {
+        format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+                (import "wasi_snapshot_preview1" "poll_oneoff"
+                    (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hello\n")
+                (func (export "_start")
+                    (i32.store (i32.const 0) (i32.const 16))
+                    (i32.store (i32.const 4) (i32.const 6))
+                    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+                    {body}))"#
+        )
+    }
+
+    /// Sets up an executor running the module in place of the interpreter, with a one second
+    /// timeout and 1 MiB of memory, in a directory of its own.
+    fn executor(body: &str) -> (WasiExecutor, PathBuf) {
+        let dir = env::temp_dir().join(format!("compiler-bot-wasi-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("jobs")).unwrap();
+        let module_path = dir.join("module.wat");
+        fs::write(&module_path, module(body)).unwrap();
+
+        let config = format!(
+            r#"
+            [languages.python]
+            timeout_duration = 1
+            termination_grace_period = 1
+            memory_limit = "1m"
+
+            [wasi]
+            work_dir = "{}"
+            "#,
+            dir.join("jobs").display()
+        );
+        let config = toml::from_str::<BotConfig>(&config).unwrap();
+        let executor = WasiExecutor::from_config(&config, module_path.to_str().unwrap()).unwrap();
+
+        (executor, dir)
+    }
+
+    fn request() -> ExecutionRequest {
+        ExecutionRequest {
+            language: LANGUAGE.into(),
+            files: vec![SourceFile {
+                name: "main.py".into(),
+                contents: "print('hello')".into(),
+            }],
+            stdin: String::new(),
+        }
+    }
+
+    fn job_count(dir: &Path) -> usize {
+        fs::read_dir(dir.join("jobs")).unwrap().count()
+    }
+
+    async fn run(body: &str) -> ExecutionResult {
+        let (executor, dir) = executor(body);
+        let result = executor.execute(&request()).await.unwrap();
+
+        // The job's files are gone once it has finished
+        assert_eq!(job_count(&dir), 0);
+        fs::remove_dir_all(&dir).unwrap();
+
+        result
+    }
+
+    #[tokio::test]
+    async fn reports_exit_codes() {
+        let result = run("(call $proc_exit (i32.const 3))").await;
+
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.exit_code, Some(3));
+        assert_eq!(result.stdout, "hello\n");
+        assert_eq!(result.usage.cpu_time, None);
+        assert_eq!(result.usage.peak_memory, Some(64 * 1024));
+    }
+
+    #[tokio::test]
+    async fn returning_from_start_is_success() {
+        let result = run("").await;
+
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn interrupts_runs_at_the_timeout() {
+        let result = run("(loop $forever (br $forever))").await;
+
+        assert_eq!(result.status, ExecutionStatus::TimedOut);
+        assert_eq!(result.exit_code, Some(124));
+        // Output from before the timeout is kept
+        assert_eq!(result.stdout, "hello\n");
+    }
+
+    #[tokio::test]
+    async fn growing_memory_past_the_limit_is_out_of_memory() {
+        // 64 MiB at once, far past the limit
+        let result = run("(drop (memory.grow (i32.const 1024)))").await;
+
+        assert_eq!(
+            result.status,
+            ExecutionStatus::OutOfMemory {
+                memory_limit: "1m".into()
+            }
+        );
+        assert_eq!(result.exit_code, None);
+    }
+
+    #[tokio::test]
+    async fn traps_are_reported_as_crashes() {
+        let result = run("unreachable").await;
+
+        assert_eq!(result.status, ExecutionStatus::Completed);
+        assert_eq!(result.exit_code, Some(1));
+        assert!(
+            result.stderr.contains("Interpreter crashed"),
+            "{}",
+            result.stderr
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_stuck_in_host_calls_are_given_up_on() {
+        // Sleeps for 3 seconds with poll_oneoff, past the 2 second deadline, where the
+        // interpreter can't be interrupted
+        let body = r#"
+            (i64.store8 offset=8 (i32.const 64) (i64.const 0))
+            (i32.store offset=16 (i32.const 64) (i32.const 1))
+            (i64.store offset=24 (i32.const 64) (i64.const 3000000000))
+            (drop (call $poll_oneoff (i32.const 64) (i32.const 128) (i32.const 1) (i32.const 192)))
+        "#;
+        let (executor, dir) = executor(body);
+
+        let result = executor.execute(&request()).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::TimedOut);
+        assert_eq!(result.exit_code, Some(124));
+        assert_eq!(result.stdout, "hello\n");
+
+        // The job's files are kept until the interpreter gives them up
+        assert_eq!(job_count(&dir), 1);
+        assert_eq!(executor.interpreter.stuck.load(Ordering::Relaxed), 1);
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(job_count(&dir), 0);
+        assert_eq!(executor.interpreter.stuck.load(Ordering::Relaxed), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
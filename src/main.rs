@@ -18,48 +18,158 @@
 
 #![deny(warnings)]
 
-use std::{env, error::Error};
+use std::{env, error::Error, process, sync::Arc};
 
 use dotenvy::dotenv;
-use poise::{Context, Framework, FrameworkOptions, PrefixFrameworkOptions};
+use poise::{Context, Framework, FrameworkError, FrameworkOptions, PrefixFrameworkOptions};
 use serenity::client::ClientBuilder;
+use tracing::level_filters::LevelFilter;
 
 use crate::{
     commands::{compile, info},
+    compiler::Compiler,
+    config::BotConfig,
     prelude::*,
+    quota::QuotaTracker,
+    rate_limit::RateLimiter,
+    scheduler::Scheduler,
 };
 
+#[cfg(feature = "api")]
+mod api;
+mod cli;
 mod commands;
+mod compiler;
 mod config;
-mod docker_executor;
+mod executors;
+mod matrix;
 mod prelude;
+mod quota;
+mod rate_limit;
+mod render;
 mod runners;
+mod scheduler;
 mod utils;
+mod worker;
 
 type CompilerBotError = Box<dyn Error + Send + Sync>;
-type CompilerBotContext<'a> = Context<'a, (), CompilerBotError>;
+type CompilerBotContext<'a> = Context<'a, Data, CompilerBotError>;
+
+/// State shared by every command.
+pub struct Data {
+    pub compiler: Arc<Compiler>,
+}
 
 #[tokio::main]
 pub async fn main() {
     dotenv().ok();
 
-    tracing::subscriber::set_global_default(utils::subscriber()).unwrap();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let command = args.first().map(String::as_str);
+    let is_cli = matches!(command, Some("run" | "languages"));
+
+    // Keep the CLI's output readable by only logging problems
+    let level = if is_cli {
+        LevelFilter::WARN
+    } else {
+        LevelFilter::TRACE
+    };
+    tracing::subscriber::set_global_default(utils::subscriber(level)).unwrap();
+
+    let config = match BotConfig::load() {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::error!("{error}");
+            return;
+        }
+    };
+
+    // Serve jobs for a bot on another machine instead of connecting to Discord
+    if command == Some("worker") {
+        if let Err(error) = worker::serve(config).await {
+            tracing::error!("{error}");
+        }
+        return;
+    }
+
+    // Run a snippet from the terminal, which needs no bot token
+    if is_cli {
+        let status = cli::main(config, &args).await.unwrap_or_else(|error| {
+            eprintln!("{error}");
+            2
+        });
+        process::exit(status);
+    }
+
+    let executor = executors::from_config(&config);
+
+    let scheduler = Arc::new(Scheduler::new(config.scheduler));
+
+    #[cfg(feature = "api")]
+    if config.api.enabled {
+        let api = api::serve(
+            config.api,
+            config.input.clone(),
+            config.rate_limit.clone(),
+            executor.clone(),
+            scheduler.clone(),
+        );
+        tokio::spawn(async move {
+            if let Err(error) = api.await {
+                tracing::error!("{error}");
+            }
+        });
+    }
+    #[cfg(not(feature = "api"))]
+    if config.api.enabled {
+        tracing::warn!("Ignoring api.enabled, the bot was built without the api feature");
+    }
+
+    let compiler = Arc::new(Compiler {
+        executor,
+        input: config.input,
+        output: config.output,
+        scheduler,
+        rate_limiter: RateLimiter::new(config.rate_limit),
+        quotas: QuotaTracker::new(config.quota),
+    });
+
+    let matrix = config
+        .matrix
+        .enabled
+        .then(|| matrix::run(config.matrix, compiler.clone()));
 
     let Ok(token) = env::var("BOT_TOKEN") else {
-        tracing::error!("BOT_TOKEN environment variable is not set");
+        // Matrix can be served on its own
+        match matrix {
+            Some(matrix) => {
+                if let Err(error) = matrix.await {
+                    tracing::error!("{error}");
+                }
+            }
+            None => tracing::error!("BOT_TOKEN environment variable is not set"),
+        }
         return;
     };
+    if let Some(matrix) = matrix {
+        tokio::spawn(async move {
+            if let Err(error) = matrix.await {
+                tracing::error!("{error}");
+            }
+        });
+    }
 
-    let commands = vec![info::help(), compile::compile(), info::languages()];
+    let commands = vec![
+        info::help(),
+        compile::compile(),
+        info::languages(),
+        commands::quota::quota(),
+    ];
 
     let framework = Framework::builder()
         .options(FrameworkOptions {
             commands,
-            on_error: |_| {
-                Box::pin(async move {
-                    // TODO: add error handler
-                })
-            },
+            on_error: |error| Box::pin(on_error(error)),
             prefix_options: PrefixFrameworkOptions {
                 prefix: Some("!".into()), // TODO: bikeshed on prefix
                 ..Default::default()
@@ -69,7 +179,7 @@ pub async fn main() {
         .setup(move |context, _, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(context, &framework.options().commands).await?;
-                Ok(())
+                Ok(Data { compiler })
             })
         })
         .build();
@@ -81,3 +191,29 @@ pub async fn main() {
 
     client.unwrap().start().await.unwrap()
 }
+
+async fn on_error(error: FrameworkError<'_, Data, CompilerBotError>) {
+    match error {
+        // Checks only fail with errors meant for the user, such as being rate limited
+        FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            if let Err(e) = ctx.say(format!("⏳ {error}")).await {
+                tracing::error!("Failed to send check failure reply: {e}");
+            }
+        }
+        FrameworkError::Command { error, ctx, .. } => {
+            tracing::error!("Error in command {}: {error}", ctx.command().name);
+            if let Err(e) = ctx.say(format!("❌ {error}")).await {
+                tracing::error!("Failed to send error reply: {e}");
+            }
+        }
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                tracing::error!("Failed to handle error: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{Client, Method, RequestBuilder};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::utils::percent_encode;
+
+/// How long the homeserver may hold a sync open waiting for new events.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time allowed for a request on top of how long the homeserver may hold it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client for the parts of the Matrix client-server API the bot uses, logged in with an access
+/// token.
+pub struct MatrixClient {
+    http: Client,
+    homeserver: String,
+    token: String,
+    /// Prefix making transaction IDs unique across restarts, which the homeserver would
+    /// otherwise take for retries of earlier messages.
+    session: u64,
+    next_transaction: AtomicU64,
+}
+
+#[derive(Deserialize)]
+pub struct Sync {
+    pub next_batch: String,
+    #[serde(default)]
+    pub rooms: Rooms,
+}
+
+#[derive(Default, Deserialize)]
+pub struct Rooms {
+    #[serde(default)]
+    pub join: HashMap<String, JoinedRoom>,
+    #[serde(default)]
+    pub invite: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+pub struct JoinedRoom {
+    #[serde(default)]
+    pub timeline: Timeline,
+}
+
+#[derive(Default, Deserialize)]
+pub struct Timeline {
+    #[serde(default)]
+    pub events: Vec<Event>,
+}
+
+#[derive(Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub sender: String,
+    pub event_id: String,
+    #[serde(default)]
+    pub content: Value,
+}
+
+#[derive(Deserialize)]
+struct MatrixError {
+    errcode: String,
+    error: Option<String>,
+}
+
+impl MatrixClient {
+    pub fn new(homeserver: &str, token: String) -> Self {
+        let session = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            http: Client::new(),
+            homeserver: homeserver.trim_end_matches('/').into(),
+            token,
+            session,
+            next_transaction: AtomicU64::new(0),
+        }
+    }
+
+    /// ID of the user the access token belongs to.
+    pub async fn whoami(&self) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct WhoAmI {
+            user_id: String,
+        }
+
+        let request = self.request(Method::GET, "/_matrix/client/v3/account/whoami");
+        Ok(send::<WhoAmI>(request).await?.user_id)
+    }
+
+    /// Waits for events after `since`, or returns where the timeline is now without any past
+    /// messages when `since` is `None`.
+    pub async fn sync(&self, since: Option<&str>) -> Result<Sync, String> {
+        let filter = match since {
+            Some(_) => json!({
+                "presence": { "types": [] },
+                "account_data": { "types": [] },
+                "room": {
+                    "timeline": { "types": ["m.room.message"] },
+                    "state": { "lazy_load_members": true },
+                    "ephemeral": { "types": [] },
+                    "account_data": { "types": [] },
+                },
+            }),
+            None => json!({ "room": { "timeline": { "limit": 0 } } }),
+        };
+
+        let mut query = vec![("filter", filter.to_string())];
+        if let Some(since) = since {
+            query.push(("since", since.into()));
+            query.push(("timeout", SYNC_TIMEOUT.as_millis().to_string()));
+        }
+
+        let request = self
+            .request(Method::GET, "/_matrix/client/v3/sync")
+            .query(&query)
+            .timeout(SYNC_TIMEOUT + REQUEST_TIMEOUT);
+        send(request).await
+    }
+
+    pub async fn join(&self, room: &str) -> Result<(), String> {
+        let path = format!("/_matrix/client/v3/join/{}", percent_encode(room));
+        send::<Value>(self.request(Method::POST, &path).json(&json!({}))).await?;
+        Ok(())
+    }
+
+    /// Sends a message event to the room, returning its event ID.
+    pub async fn send_message(&self, room: &str, content: &Value) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct Sent {
+            event_id: String,
+        }
+
+        let transaction = self.next_transaction.fetch_add(1, Ordering::Relaxed);
+        let path = format!(
+            "/_matrix/client/v3/rooms/{}/send/m.room.message/{}-{transaction}",
+            percent_encode(room),
+            self.session
+        );
+        let request = self.request(Method::PUT, &path).json(content);
+        Ok(send::<Sent>(request).await?.event_id)
+    }
+
+    /// Uploads a file to the homeserver's media repository, returning its `mxc://` URI.
+    pub async fn upload(
+        &self,
+        filename: &str,
+        content_type: &str,
+        contents: Vec<u8>,
+    ) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct Uploaded {
+            content_uri: String,
+        }
+
+        let request = self
+            .request(Method::POST, "/_matrix/media/v3/upload")
+            .query(&[("filename", filename)])
+            .header("Content-Type", content_type)
+            .body(contents);
+        Ok(send::<Uploaded>(request).await?.content_uri)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{path}", self.homeserver))
+            .bearer_auth(&self.token)
+            .timeout(REQUEST_TIMEOUT)
+    }
+}
+
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the homeserver: {e}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let message = match response.json::<MatrixError>().await {
+            Ok(MatrixError {
+                errcode,
+                error: Some(error),
+            }) => format!("{errcode}: {error}"),
+            Ok(MatrixError { errcode, .. }) => errcode,
+            Err(_) => status.to_string(),
+        };
+        return Err(format!("Homeserver refused the request: {message}"));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid response from the homeserver: {e}"))
+}
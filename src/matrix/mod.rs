@@ -0,0 +1,526 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    env,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use self::client::{Event, MatrixClient};
+use crate::{
+    compiler::{Compiler, Origin, Reply},
+    config::MatrixConfig,
+    executors::OutputFile,
+    render::{Content, Report},
+    utils::is_image,
+};
+
+mod client;
+
+/// Environment variable holding the access token the bot logs in to Matrix with.
+pub const TOKEN_VAR: &str = "MATRIX_TOKEN";
+
+/// Commands are messages starting with this, as on Discord.
+const PREFIX: &str = "!";
+/// Wait before syncing again after a sync fails.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const HELP: &str = "Commands:
+!compile [language] <code block> - Compile and run code
+!languages - List supported languages for compilation
+!help - Show this help menu
+
+Projects of several files can be sent as code blocks whose first line is a `// file: <name>` label.";
+
+struct Matrix {
+    client: MatrixClient,
+    compiler: Arc<Compiler>,
+    user_id: String,
+    rooms: Vec<String>,
+}
+
+/// Answers commands sent in Matrix rooms, running code with the same executor, queue, rate
+/// limits and quotas as on Discord. Only code blocks are run, since files are sent as messages
+/// of their own on Matrix.
+pub async fn run(config: MatrixConfig, compiler: Arc<Compiler>) -> Result<(), String> {
+    let token = env::var(TOKEN_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| format!("{TOKEN_VAR} environment variable is not set"))?;
+
+    serve(config, token, compiler).await
+}
+
+/// Logs in with the access token and answers commands until the bot stops.
+async fn serve(config: MatrixConfig, token: String, compiler: Arc<Compiler>) -> Result<(), String> {
+    let client = MatrixClient::new(&config.homeserver, token);
+    let user_id = client.whoami().await?;
+    tracing::info!("Logged in to Matrix as {user_id}");
+
+    let matrix = Arc::new(Matrix {
+        client,
+        compiler,
+        user_id,
+        rooms: config.rooms,
+    });
+
+    // Start from now rather than answering messages sent while the bot was away
+    let sync = matrix.client.sync(None).await?;
+    matrix.join_invited(sync.rooms.invite.into_keys()).await;
+    let mut since = sync.next_batch;
+
+    loop {
+        let sync = match matrix.client.sync(Some(&since)).await {
+            Ok(sync) => sync,
+            Err(error) => {
+                tracing::warn!("Matrix sync failed: {error}");
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+        since = sync.next_batch;
+
+        matrix.join_invited(sync.rooms.invite.into_keys()).await;
+        for (room, joined) in sync.rooms.join {
+            if !matrix.is_allowed(&room) {
+                continue;
+            }
+
+            for event in joined.timeline.events {
+                if event.kind != "m.room.message" || event.sender == matrix.user_id {
+                    continue;
+                }
+
+                let matrix = matrix.clone();
+                let room = room.clone();
+                tokio::spawn(async move { matrix.handle(&room, event).await });
+            }
+        }
+    }
+}
+
+impl Matrix {
+    fn is_allowed(&self, room: &str) -> bool {
+        self.rooms.is_empty() || self.rooms.iter().any(|allowed| allowed == room)
+    }
+
+    async fn join_invited(&self, rooms: impl Iterator<Item = String>) {
+        for room in rooms.filter(|room| self.is_allowed(room)) {
+            match self.client.join(&room).await {
+                Ok(()) => tracing::info!("Joined Matrix room {room}"),
+                Err(error) => tracing::warn!("Failed to join Matrix room {room}: {error}"),
+            }
+        }
+    }
+
+    async fn handle(&self, room: &str, event: Event) {
+        // Edits of earlier messages aren't new commands
+        let content = &event.content;
+        if content["msgtype"] != "m.text" || content.get("m.new_content").is_some() {
+            return;
+        }
+        let Some(body) = content["body"].as_str() else {
+            return;
+        };
+        let Some((command, rest)) =
+            strip_reply_fallback(body)
+                .strip_prefix(PREFIX)
+                .map(|command| {
+                    command
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((command, ""))
+                })
+        else {
+            return;
+        };
+
+        let result = match command {
+            "compile" => self.compile(room, &event, rest.trim_start()).await,
+            "languages" => self.languages(room, &event).await,
+            "help" => self.notice(room, &event, HELP).await,
+            _ => return,
+        };
+        if let Err(error) = result {
+            tracing::error!("Error in Matrix command {command}: {error}");
+            if let Err(e) = self.notice(room, &event, &format!("❌ {error}")).await {
+                tracing::error!("Failed to send error reply: {e}");
+            }
+        }
+    }
+
+    async fn compile(&self, room: &str, event: &Event, arguments: &str) -> Result<(), String> {
+        let origin = Origin {
+            user: numeric_id(&event.sender),
+            channel: numeric_id(room),
+            guild: None,
+        };
+
+        if let Err(error) = self.compiler.check_rate_limit(&origin) {
+            return self.notice(room, event, &format!("⏳ {error}")).await;
+        }
+        if let Err(error) = self.compiler.check_quota(&origin) {
+            return self.notice(room, event, &format!("⏳ {error}")).await;
+        }
+
+        // The language is the first word, unless the code block comes right away
+        let (language, code) = match arguments.split_once(char::is_whitespace) {
+            Some((language, code)) if !language.starts_with("```") => {
+                (Some(language.to_string()), code)
+            }
+            _ => (None, arguments),
+        };
+
+        let job = match self.compiler.parse(language, Some(code), &[]).await {
+            Ok(job) => job,
+            Err(error) => return self.notice(room, event, &error).await,
+        };
+
+        let mut reply = NoticeReply {
+            matrix: self,
+            room,
+            reply_to: &event.event_id,
+            event_id: None,
+        };
+        self.compiler.run(&origin, job, &mut reply).await
+    }
+
+    async fn languages(&self, room: &str, event: &Event) -> Result<(), String> {
+        let language_list = self
+            .compiler
+            .executor
+            .supported_languages()
+            .iter()
+            .map(|language| format!("`{language}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        self.notice(
+            room,
+            event,
+            &format!("Supported Programming Languages\n{language_list}"),
+        )
+        .await
+    }
+
+    /// Replies to the event with a plain notice.
+    async fn notice(&self, room: &str, event: &Event, text: &str) -> Result<(), String> {
+        let content = json!({
+            "msgtype": "m.notice",
+            "body": text,
+            "m.relates_to": { "m.in_reply_to": { "event_id": event.event_id } },
+        });
+        self.client.send_message(room, &content).await?;
+
+        Ok(())
+    }
+}
+
+/// Shows reports as a notice replying to the command, sent with the first one and edited after.
+/// Output files are sent as messages of their own once the job has finished.
+struct NoticeReply<'a> {
+    matrix: &'a Matrix,
+    room: &'a str,
+    reply_to: &'a str,
+    event_id: Option<String>,
+}
+
+#[async_trait]
+impl Reply for NoticeReply<'_> {
+    async fn show(&mut self, report: Report, files: Vec<OutputFile>) -> Result<(), String> {
+        let client = &self.matrix.client;
+        let body = report.to_plain_text();
+        let formatted_body = html(&report);
+        let mut content = json!({
+            "msgtype": "m.notice",
+            "body": body,
+            "format": "org.matrix.custom.html",
+            "formatted_body": formatted_body,
+        });
+
+        match &self.event_id {
+            Some(event_id) => {
+                let edit = json!({
+                    "msgtype": "m.notice",
+                    "body": format!("* {body}"),
+                    "format": "org.matrix.custom.html",
+                    "formatted_body": format!("* {formatted_body}"),
+                    "m.new_content": content,
+                    "m.relates_to": { "rel_type": "m.replace", "event_id": event_id },
+                });
+                client.send_message(self.room, &edit).await?;
+            }
+            None => {
+                content["m.relates_to"] = json!({ "m.in_reply_to": { "event_id": self.reply_to } });
+                self.event_id = Some(client.send_message(self.room, &content).await?);
+            }
+        }
+
+        for file in files {
+            let (msgtype, content_type) = match file.name.rsplit_once('.') {
+                Some((_, extension)) if is_image(&file.name) => {
+                    let extension = extension.to_ascii_lowercase();
+                    let subtype = if extension == "jpg" {
+                        "jpeg"
+                    } else {
+                        &extension
+                    };
+                    ("m.image", format!("image/{subtype}"))
+                }
+                _ => ("m.file", "application/octet-stream".into()),
+            };
+
+            let size = file.contents.len();
+            let url = client
+                .upload(&file.name, &content_type, file.contents)
+                .await?;
+            let content = json!({
+                "msgtype": msgtype,
+                "body": file.name,
+                "filename": file.name,
+                "url": url,
+                "info": { "mimetype": content_type, "size": size },
+            });
+            client.send_message(self.room, &content).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lays the report out as HTML, with code and output in code blocks.
+fn html(report: &Report) -> String {
+    let mut html = format!("<p><strong>{}</strong></p>", escape(&report.title));
+    if let Some(description) = &report.description {
+        html.push_str(&content_html(description));
+    }
+    for field in &report.fields {
+        html.push_str(&format!("<p><em>{}</em></p>", escape(&field.name)));
+        html.push_str(&content_html(&field.value));
+    }
+    if let Some(footer) = &report.footer {
+        html.push_str(&format!("<p><sub>{}</sub></p>", escape(footer)));
+    }
+
+    html
+}
+
+fn content_html(content: &Content) -> String {
+    match content {
+        Content::Text(text) => format!("<p>{}</p>", escape(text.trim_end()).replace('\n', "<br>")),
+        Content::Code { language, code } if language.is_empty() => {
+            format!("<pre><code>{}</code></pre>", escape(code))
+        }
+        Content::Code { language, code } => format!(
+            "<pre><code class=\"language-{}\">{}</code></pre>",
+            escape(language),
+            escape(code)
+        ),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Drops the quote of the original message that clients put at the top of replies.
+fn strip_reply_fallback(body: &str) -> &str {
+    if !body.starts_with("> ") {
+        return body;
+    }
+
+    body.split_once("\n\n").map_or(body, |(_, rest)| rest)
+}
+
+/// Turns a Matrix user or room ID into a number, which is what rate limits, quotas and the queue
+/// key on.
+fn numeric_id(id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use axum::{
+        Json, Router,
+        extract::{Path, State},
+        http::{HeaderMap, Uri},
+        routing::{get, put},
+    };
+    use serde_json::Value;
+    use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+    use crate::{
+        config::{InputConfig, OutputConfig, QuotaConfig, RateLimitConfig, SchedulerConfig},
+        executors::MockExecutor,
+        quota::QuotaTracker,
+        rate_limit::RateLimiter,
+        scheduler::Scheduler,
+    };
+
+    const ROOM: &str = "!room:example.org";
+    const BOT: &str = "@bot:example.org";
+
+    /// A homeserver with a single room, where a user asks for some code to be run once the bot
+    /// has caught up. Messages the bot sends are passed on with the room they were sent to.
+    fn spawn_homeserver() -> (String, UnboundedReceiver<(String, Value)>) {
+        async fn whoami(headers: HeaderMap) -> Json<Value> {
+            assert_eq!(headers["authorization"], "Bearer token");
+            Json(json!({ "user_id": BOT }))
+        }
+
+        async fn sync(uri: Uri) -> Json<Value> {
+            let since = uri
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("since="));
+            match since {
+                None => Json(json!({ "next_batch": "s1" })),
+                Some("s1") => Json(json!({
+                    "next_batch": "s2",
+                    "rooms": { "join": { ROOM: { "timeline": { "events": [
+                        {
+                            "type": "m.room.message",
+                            "sender": BOT,
+                            "event_id": "$own",
+                            "content": { "msgtype": "m.text", "body": "!help" },
+                        },
+                        {
+                            "type": "m.room.message",
+                            "sender": "@user:example.org",
+                            "event_id": "$compile",
+                            "content": {
+                                "msgtype": "m.text",
+                                "body": "!compile python ```py\nprint('hi')\n```",
+                            },
+                        },
+                    ] } } } },
+                })),
+                // Nothing more happens, so hold the sync open as a homeserver would
+                Some(since) => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Json(json!({ "next_batch": since }))
+                }
+            }
+        }
+
+        async fn send(
+            State(sent): State<UnboundedSender<(String, Value)>>,
+            Path((room, transaction)): Path<(String, String)>,
+            Json(content): Json<Value>,
+        ) -> Json<Value> {
+            let _ = sent.send((room, content));
+            Json(json!({ "event_id": format!("$sent-{transaction}") }))
+        }
+
+        let (sent, received) = mpsc::unbounded_channel();
+        let app = Router::new()
+            .route("/_matrix/client/v3/account/whoami", get(whoami))
+            .route("/_matrix/client/v3/sync", get(sync))
+            .route(
+                "/_matrix/client/v3/rooms/:room/send/m.room.message/:transaction",
+                put(send),
+            )
+            .with_state(sent);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        (format!("http://{address}"), received)
+    }
+
+    fn compiler() -> Arc<Compiler> {
+        Arc::new(Compiler {
+            executor: Arc::new(MockExecutor::new()),
+            input: InputConfig::default(),
+            output: OutputConfig::default(),
+            scheduler: Arc::new(Scheduler::new(SchedulerConfig::default())),
+            rate_limiter: RateLimiter::new(RateLimitConfig::default()),
+            quotas: QuotaTracker::new(QuotaConfig::default()),
+        })
+    }
+
+    #[tokio::test]
+    async fn answers_compile_commands_with_notices() {
+        let (homeserver, mut sent) = spawn_homeserver();
+        let config = MatrixConfig {
+            enabled: true,
+            homeserver,
+            rooms: Vec::new(),
+        };
+        let bot = tokio::spawn(serve(config, "token".into(), compiler()));
+
+        let mut next = async || {
+            tokio::time::timeout(Duration::from_secs(10), sent.recv())
+                .await
+                .expect("the bot didn't reply")
+                .unwrap()
+        };
+
+        // The job is shown as running in a notice replying to the command
+        let (room, pending) = next().await;
+        assert_eq!(room, ROOM);
+        assert_eq!(pending["msgtype"], "m.notice");
+        assert_eq!(
+            pending["m.relates_to"]["m.in_reply_to"]["event_id"],
+            "$compile"
+        );
+        assert!(
+            pending["body"]
+                .as_str()
+                .unwrap()
+                .contains("Executing python code")
+        );
+
+        // Then the notice is edited to show the result
+        let (room, result) = next().await;
+        assert_eq!(room, ROOM);
+        assert_eq!(result["msgtype"], "m.notice");
+        assert_eq!(result["m.relates_to"]["rel_type"], "m.replace");
+        assert!(
+            result["m.relates_to"]["event_id"]
+                .as_str()
+                .unwrap()
+                .starts_with("$sent-")
+        );
+        let body = result["m.new_content"]["body"].as_str().unwrap();
+        assert!(body.contains("Execution result"), "{body}");
+        assert!(body.contains("main.py (11 bytes)"), "{body}");
+
+        // Nothing else was sent, such as an answer to the bot's own `!help`
+        assert!(sent.try_recv().is_err());
+        bot.abort();
+    }
+}
@@ -0,0 +1,258 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::QuotaConfig;
+
+/// Who CPU time is charged to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Account {
+    User(u64),
+    Guild(u64),
+}
+
+/// CPU time used by each user and guild over a rolling window, such as the last day. Usage is
+/// only kept in memory, so restarting the bot clears it.
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// When each run finished and how much CPU time it used, oldest first.
+    usage: HashMap<Account, VecDeque<(Instant, Duration)>>,
+    /// Quotas set by admins in place of the configured ones.
+    limits: HashMap<Account, Duration>,
+}
+
+#[derive(Debug)]
+pub struct QuotaStatus {
+    pub used: Duration,
+    /// The account's quota, or `None` if it has none.
+    pub limit: Option<Duration>,
+    /// Time until the oldest run in the window stops counting.
+    pub frees_up_in: Option<Duration>,
+}
+
+impl QuotaStatus {
+    pub fn remaining(&self) -> Option<Duration> {
+        self.limit.map(|limit| limit.saturating_sub(self.used))
+    }
+}
+
+/// Returned when a job is refused because the user or guild has used up its CPU time.
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub account: Account,
+    pub frees_up_in: Option<Duration>,
+}
+
+impl Display for QuotaExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.account {
+            Account::User(_) => write!(f, "You have used up your CPU time quota")?,
+            Account::Guild(_) => write!(f, "This server has used up its CPU time quota")?,
+        }
+
+        match self.frees_up_in {
+            Some(duration) => write!(f, ", more frees up in {}", format_duration(duration)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for QuotaExceeded {}
+
+impl QuotaTracker {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Checks that neither the user nor the guild has used up its quota.
+    pub fn check(&self, user: u64, guild: Option<u64>) -> Result<(), QuotaExceeded> {
+        let accounts = [Some(Account::User(user)), guild.map(Account::Guild)];
+        for account in accounts.into_iter().flatten() {
+            let status = self.status(account);
+            if status
+                .remaining()
+                .is_some_and(|remaining| remaining.is_zero())
+            {
+                return Err(QuotaExceeded {
+                    account,
+                    frees_up_in: status.frees_up_in,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Charges a finished run's CPU time to the user and guild.
+    pub fn record(&self, user: u64, guild: Option<u64>, cpu_time: Duration) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window);
+        let mut state = self.state.lock().unwrap();
+
+        let accounts = [Some(Account::User(user)), guild.map(Account::Guild)];
+        for account in accounts.into_iter().flatten() {
+            let usage = state.usage.entry(account).or_default();
+            usage.retain(|(finished_at, _)| now.duration_since(*finished_at) < window);
+            usage.push_back((now, cpu_time));
+        }
+    }
+
+    pub fn status(&self, account: Account) -> QuotaStatus {
+        let mut state = self.state.lock().unwrap();
+        let window = Duration::from_secs(self.config.window);
+
+        let limit = state.limits.get(&account).copied().or_else(|| {
+            let seconds = match account {
+                Account::User(_) => self.config.user_cpu_seconds,
+                Account::Guild(_) => self.config.guild_cpu_seconds,
+            };
+            (seconds > 0).then(|| Duration::from_secs(seconds))
+        });
+
+        let Some(usage) = state.usage.get_mut(&account) else {
+            return QuotaStatus {
+                used: Duration::ZERO,
+                limit,
+                frees_up_in: None,
+            };
+        };
+
+        // Forget runs that have left the window
+        while usage
+            .front()
+            .is_some_and(|(finished_at, _)| finished_at.elapsed() >= window)
+        {
+            usage.pop_front();
+        }
+
+        let status = QuotaStatus {
+            used: usage.iter().map(|(_, cpu_time)| *cpu_time).sum(),
+            limit,
+            frees_up_in: usage
+                .front()
+                .map(|(finished_at, _)| window.saturating_sub(finished_at.elapsed())),
+        };
+        if usage.is_empty() {
+            state.usage.remove(&account);
+        }
+
+        status
+    }
+
+    /// Forgets the CPU time the account has used.
+    pub fn reset(&self, account: Account) {
+        self.state.lock().unwrap().usage.remove(&account);
+    }
+
+    /// Gives the account its own quota in place of the configured one.
+    pub fn set_limit(&self, account: Account, limit: Duration) {
+        self.state.lock().unwrap().limits.insert(account, limit);
+    }
+}
+
+/// Formats a duration as hours and minutes, or seconds when under a minute.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds % 3600 / 60) {
+        (0, 0) => format!("{seconds}s"),
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> QuotaTracker {
+        QuotaTracker::new(QuotaConfig {
+            user_cpu_seconds: 10,
+            guild_cpu_seconds: 15,
+            window: 3600,
+        })
+    }
+
+    #[test]
+    fn usage_is_charged_to_the_user_and_guild() {
+        let quotas = tracker();
+        quotas.record(1, Some(7), Duration::from_secs(4));
+        quotas.record(2, Some(7), Duration::from_secs(6));
+
+        let user = quotas.status(Account::User(1));
+        assert_eq!(user.used, Duration::from_secs(4));
+        assert_eq!(user.remaining(), Some(Duration::from_secs(6)));
+        assert_eq!(
+            quotas.status(Account::Guild(7)).used,
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn used_up_quotas_refuse_jobs() {
+        let quotas = tracker();
+        quotas.record(1, None, Duration::from_secs(10));
+        let exceeded = quotas.check(1, None).unwrap_err();
+        assert_eq!(exceeded.account, Account::User(1));
+        assert!(exceeded.frees_up_in.is_some());
+
+        quotas.record(2, Some(7), Duration::from_secs(9));
+        quotas.record(3, Some(7), Duration::from_secs(9));
+        assert_eq!(
+            quotas.check(4, Some(7)).unwrap_err().account,
+            Account::Guild(7)
+        );
+        assert!(quotas.check(4, None).is_ok());
+    }
+
+    #[test]
+    fn overrides_and_resets_apply_to_one_account() {
+        let quotas = tracker();
+        quotas.record(1, None, Duration::from_secs(10));
+        quotas.record(2, None, Duration::from_secs(10));
+
+        quotas.set_limit(Account::User(1), Duration::from_secs(20));
+        assert!(quotas.check(1, None).is_ok());
+        assert!(quotas.check(2, None).is_err());
+
+        quotas.reset(Account::User(2));
+        assert!(quotas.check(2, None).is_ok());
+        assert_eq!(quotas.status(Account::User(2)).used, Duration::ZERO);
+    }
+
+    #[test]
+    fn durations_are_formatted_roughly() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(150)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 60)), "3h 1m");
+    }
+}
@@ -0,0 +1,256 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::{BucketConfig, RateLimitConfig};
+
+/// Buckets kept before full ones, which are no different from fresh ones, are thrown away.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Token buckets limiting how often jobs can be submitted by each user, in each channel and in
+/// each guild, and with each key of the HTTP API.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(Scope, u64), Bucket>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Scope {
+    User,
+    Channel,
+    Guild,
+    ApiKey,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Returned when a job is refused for going over a rate limit.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl Display for RateLimited {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "You're running code too often, try again in {}s",
+            self.retry_after.as_secs_f64().ceil()
+        )
+    }
+}
+
+impl Error for RateLimited {}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether any roles are exempt, making it worth looking up the user's roles.
+    pub fn has_exempt_roles(&self) -> bool {
+        !self.config.exempt_roles.is_empty()
+    }
+
+    /// Whether any of the roles is exempt from rate limiting.
+    pub fn is_exempt(&self, roles: &[u64]) -> bool {
+        roles
+            .iter()
+            .any(|role| self.config.exempt_roles.contains(role))
+    }
+
+    /// Takes a token from the user's, channel's and guild's buckets, or from none of them if any
+    /// is empty, in which case the wait until all of them have one is returned.
+    pub fn check(&self, user: u64, channel: u64, guild: Option<u64>) -> Result<(), RateLimited> {
+        self.take(&[
+            (Scope::User, Some(user), &self.config.user),
+            (Scope::Channel, Some(channel), &self.config.channel),
+            (Scope::Guild, guild, &self.config.guild),
+        ])
+    }
+
+    /// Takes a token from the API key's bucket, or returns the wait until it has one.
+    pub fn check_api_key(&self, key: u64) -> Result<(), RateLimited> {
+        self.take(&[(Scope::ApiKey, Some(key), &self.config.api_key)])
+    }
+
+    fn take(&self, scopes: &[(Scope, Option<u64>, &BucketConfig)]) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let limited = scopes
+            .iter()
+            .filter_map(|&(scope, id, config)| Some((scope, id?, config)))
+            .filter(|(_, _, config)| config.capacity > 0)
+            .collect::<Vec<_>>();
+
+        let mut retry_after = Duration::ZERO;
+        for &(scope, id, config) in &limited {
+            let bucket = buckets.entry((scope, id)).or_insert_with(|| Bucket {
+                tokens: config.capacity as f64,
+                updated_at: now,
+            });
+            bucket.refill(config, now);
+
+            if bucket.tokens < 1.0 {
+                let wait = (1.0 - bucket.tokens) * config.refill_interval.max(1) as f64;
+                retry_after = retry_after.max(Duration::from_secs_f64(wait));
+            }
+        }
+
+        if !retry_after.is_zero() {
+            return Err(RateLimited { retry_after });
+        }
+
+        for &(scope, id, _) in &limited {
+            if let Some(bucket) = buckets.get_mut(&(scope, id)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|(scope, _), bucket| {
+                let config = match scope {
+                    Scope::User => &self.config.user,
+                    Scope::Channel => &self.config.channel,
+                    Scope::Guild => &self.config.guild,
+                    Scope::ApiKey => &self.config.api_key,
+                };
+                bucket.refill(config, now);
+                bucket.tokens < config.capacity as f64
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        let refilled = elapsed / config.refill_interval.max(1) as f64;
+        self.tokens = (self.tokens + refilled).min(config.capacity as f64);
+        self.updated_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(user: u32, channel: u32, guild: u32) -> RateLimiter {
+        let bucket = |capacity| BucketConfig {
+            capacity,
+            refill_interval: 10,
+        };
+        RateLimiter::new(RateLimitConfig {
+            user: bucket(user),
+            channel: bucket(channel),
+            guild: bucket(guild),
+            api_key: bucket(2),
+            exempt_roles: vec![42],
+        })
+    }
+
+    #[test]
+    fn bursts_are_allowed_up_to_the_capacity() {
+        let limiter = limiter(3, 0, 0);
+        for _ in 0..3 {
+            assert!(limiter.check(1, 1, None).is_ok());
+        }
+
+        let limited = limiter.check(1, 1, None).unwrap_err();
+        assert!(limited.retry_after > Duration::from_secs(9));
+        assert!(limited.retry_after <= Duration::from_secs(10));
+
+        // Other users have buckets of their own
+        assert!(limiter.check(2, 1, None).is_ok());
+    }
+
+    #[test]
+    fn refused_jobs_take_no_tokens() {
+        let limiter = limiter(2, 1, 0);
+        assert!(limiter.check(1, 1, None).is_ok());
+
+        // The channel is empty, so the user's second token stays put
+        assert!(limiter.check(1, 1, None).is_err());
+        assert!(limiter.check(1, 2, None).is_ok());
+        assert!(limiter.check(1, 3, None).is_err());
+    }
+
+    #[test]
+    fn guilds_are_limited_across_channels() {
+        let limiter = limiter(0, 0, 2);
+        assert!(limiter.check(1, 1, Some(7)).is_ok());
+        assert!(limiter.check(2, 2, Some(7)).is_ok());
+        assert!(limiter.check(3, 3, Some(7)).is_err());
+
+        // Direct messages have no guild to limit
+        assert!(limiter.check(3, 3, None).is_ok());
+    }
+
+    #[test]
+    fn api_keys_have_their_own_buckets() {
+        let limiter = limiter(1, 1, 1);
+        assert!(limiter.check_api_key(1).is_ok());
+        assert!(limiter.check_api_key(1).is_ok());
+        assert!(limiter.check_api_key(1).is_err());
+        assert!(limiter.check(1, 1, None).is_ok());
+    }
+
+    #[test]
+    fn buckets_refill_over_time_up_to_the_capacity() {
+        let config = BucketConfig {
+            capacity: 3,
+            refill_interval: 10,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 0.0,
+            updated_at: start,
+        };
+
+        bucket.refill(&config, start + Duration::from_secs(5));
+        assert_eq!(bucket.tokens, 0.5);
+        bucket.refill(&config, start + Duration::from_secs(20));
+        assert_eq!(bucket.tokens, 2.0);
+        bucket.refill(&config, start + Duration::from_secs(600));
+        assert_eq!(bucket.tokens, 3.0);
+    }
+
+    #[test]
+    fn exempt_roles_are_recognized() {
+        let limiter = limiter(1, 1, 1);
+        assert!(limiter.has_exempt_roles());
+        assert!(limiter.is_exempt(&[1, 42]));
+        assert!(!limiter.is_exempt(&[1, 2]));
+    }
+}
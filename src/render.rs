@@ -0,0 +1,262 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    config::OutputConfig,
+    executors::{ExecutionResult, ExecutionStatus, ResourceUsage, SourceFile},
+};
+
+/// A job's progress or result laid out as a title, fields and footer, independent of where it is
+/// shown. Discord shows it as an embed, while the CLI prints it.
+pub struct Report {
+    pub title: String,
+    pub color: u32,
+    pub description: Option<Content>,
+    pub fields: Vec<Field>,
+    pub footer: Option<String>,
+}
+
+pub struct Field {
+    pub name: String,
+    pub value: Content,
+    pub inline: bool,
+}
+
+pub enum Content {
+    Text(String),
+    /// Code or program output, highlighted as `language` where it is named.
+    Code {
+        language: String,
+        code: String,
+    },
+}
+
+impl Content {
+    /// Formats the content as Markdown, with code in a fenced block.
+    pub fn to_markdown(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Code { language, code } => format!("```{language}\n{code}\n```"),
+        }
+    }
+}
+
+impl Display for Content {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{text}"),
+            Self::Code { code, .. } => write!(f, "{code}"),
+        }
+    }
+}
+
+impl Report {
+    /// Lays the report out as plain text, with each field under its name.
+    pub fn to_plain_text(&self) -> String {
+        let mut text = self.title.clone();
+        if let Some(description) = &self.description {
+            text.push('\n');
+            text.push_str(description.to_string().trim_end());
+        }
+        for field in &self.fields {
+            text.push_str(&format!(
+                "\n\n{}:\n{}",
+                field.name,
+                field.value.to_string().trim_end()
+            ));
+        }
+        if let Some(footer) = &self.footer {
+            text.push_str(&format!("\n\n{footer}"));
+        }
+
+        text
+    }
+
+    fn field(mut self, name: &str, value: Content, inline: bool) -> Self {
+        self.fields.push(Field {
+            name: name.into(),
+            value,
+            inline,
+        });
+        self
+    }
+
+    /// A job that is waiting for its turn, or running once `position` is 0.
+    pub fn pending(
+        files: &[SourceFile],
+        language: &str,
+        position: usize,
+        output: &OutputConfig,
+    ) -> Self {
+        let report = Self {
+            title: format!("🔄 Executing {language} code"),
+            color: 0xFFFF00, // Yellow for "running"
+            description: Some(preview(files, language, output)),
+            fields: Vec::new(),
+            footer: None,
+        };
+
+        match position {
+            0 => report,
+            position => report.field(
+                "Queue position",
+                Content::Text(format!("#{position}")),
+                true,
+            ),
+        }
+    }
+
+    /// A finished run. Output files are listed, but attaching them is left to the caller.
+    pub fn result(result: &ExecutionResult, output: &OutputConfig) -> Self {
+        let (status_emoji, color) = match &result.status {
+            ExecutionStatus::Completed if result.exit_code == Some(0) => {
+                ("✅", 0x00FF00) // Green for success
+            }
+            ExecutionStatus::Completed => ("❌", 0xFF0000), // Red for error
+            ExecutionStatus::TimedOut => ("⏰", 0xFF8800),  // Orange for timeout
+            ExecutionStatus::OutputLimitExceeded => ("📜", 0xFFAA00), // Amber for flooding
+            ExecutionStatus::OutOfMemory { .. } => ("🧠", 0x9B59B6), // Purple for memory
+            ExecutionStatus::Signaled(11) => ("💥", 0x8B0000), // Dark red for segfaults
+            ExecutionStatus::Signaled(6) => ("🛑", 0xC0392B), // Crimson for aborts
+            ExecutionStatus::Signaled(8) => ("➗", 0xE74C3C), // Light red for arithmetic
+            ExecutionStatus::Signaled(_) => ("☠️", 0x5D0000), // Maroon for other signals
+        };
+
+        let title = result
+            .status
+            .description()
+            .unwrap_or_else(|| "Execution result".into());
+        let mut report = Self {
+            title: format!("{status_emoji} {title}"),
+            color,
+            description: None,
+            fields: Vec::new(),
+            footer: Some(format_usage(&result.usage)),
+        };
+
+        // Add stdout if present
+        if !result.stdout.is_empty() {
+            report = report.field("Output", output_block(&result.stdout, output), false);
+        }
+
+        // Add stderr if present
+        if !result.stderr.is_empty() {
+            report = report.field("Error", output_block(&result.stderr, output), false);
+        }
+
+        // Add execution info
+        let mut execution_info = String::new();
+        if let Some(code) = result.exit_code {
+            execution_info.push_str(&format!("Exit code: {code}\n"));
+        }
+        if let Some(description) = result.status.description() {
+            execution_info.push_str(&format!("⚠️ {description}\n"));
+        }
+        if !execution_info.is_empty()
+            && (result.exit_code != Some(0) || result.status != ExecutionStatus::Completed)
+        {
+            report = report.field("Execution Info", Content::Text(execution_info), true);
+        }
+
+        if !result.files.is_empty() {
+            let file_list = result
+                .files
+                .iter()
+                .map(|file| format!("`{}` ({} bytes)", file.name, file.contents.len()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            report = report.field("Files", Content::Text(file_list), false);
+        }
+
+        report
+    }
+
+    /// A job the executor failed to run at all.
+    pub fn failure(
+        error: &str,
+        files: &[SourceFile],
+        language: &str,
+        output: &OutputConfig,
+    ) -> Self {
+        Self {
+            title: "❌ Execution failed".into(),
+            color: 0xFF0000, // Red for error
+            description: Some(Content::Text(format!("Failed to execute {language} code"))),
+            fields: Vec::new(),
+            footer: None,
+        }
+        .field(
+            "Error",
+            Content::Code {
+                language: String::new(),
+                code: error.into(),
+            },
+            false,
+        )
+        .field("Source Code", preview(files, language, output), false)
+    }
+}
+
+fn output_block(text: &str, output: &OutputConfig) -> Content {
+    Content::Code {
+        language: String::new(),
+        code: truncate(text, output),
+    }
+}
+
+fn format_usage(usage: &ResourceUsage) -> String {
+    let mut parts = vec![format!("⏱️ {:.2}s wall", usage.wall_time.as_secs_f64())];
+    if let Some(cpu_time) = usage.cpu_time {
+        parts.push(format!("🧮 {:.2}s CPU", cpu_time.as_secs_f64()));
+    }
+    if let Some(peak_memory) = usage.peak_memory {
+        parts.push(format!(
+            "💾 {:.1} MiB peak",
+            peak_memory as f64 / (1024.0 * 1024.0)
+        ));
+    }
+
+    parts.join(" · ")
+}
+
+fn preview(files: &[SourceFile], language: &str, output: &OutputConfig) -> Content {
+    match files {
+        [file] => Content::Code {
+            language: language.into(),
+            code: truncate(&file.contents, output),
+        },
+        _ => Content::Text(
+            files
+                .iter()
+                .map(|file| format!("`{}` ({} bytes)", file.name, file.contents.len()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    }
+}
+
+fn truncate(text: &str, output: &OutputConfig) -> String {
+    if text.len() > output.max_output_length {
+        let end = text.floor_char_boundary(output.max_output_length);
+        format!("{}{}", &text[..end], output.truncate_suffix)
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,50 @@
+# Compiler-Bot: compiler bot for Unofficial.CSE
+# Copyright (C) 2025  Unofficial.CSE contributors
+#
+# Compiler-Bot is free software: you can redistribute it and/or modify
+# it under the terms of the GNU Affero General Public License as published
+# by the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# Compiler-Bot is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU Affero General Public License for more details.
+#
+# You should have received a copy of the GNU Affero General Public License
+# along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+
+"""Non-interactive matplotlib backend that saves figures to the output directory.
+
+Selected through ``MPLBACKEND=module://compiler_bot_backend``. Figures are saved as PNGs
+whenever ``plt.show()`` is called and once more for any still open when the program exits.
+"""
+
+import atexit
+import os
+import sys
+
+from matplotlib.backends.backend_agg import FigureCanvasAgg as FigureCanvas  # noqa: F401
+
+OUTPUT_DIR = "/out"
+
+_saved_figures = 0
+
+
+def show(*args, **kwargs):
+    global _saved_figures
+
+    import matplotlib.pyplot as plt
+
+    for number in plt.get_fignums():
+        _saved_figures += 1
+        path = os.path.join(OUTPUT_DIR, f"figure_{_saved_figures}.png")
+        try:
+            plt.figure(number).savefig(path)
+        except Exception as error:
+            print(f"Failed to save figure {number}: {error}", file=sys.stderr)
+
+    plt.close("all")
+
+
+atexit.register(show)
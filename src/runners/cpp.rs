@@ -23,7 +23,7 @@ pub struct Cpp;
 
 impl Language for Cpp {
     fn command(&self) -> &'static str {
-        "bash -c 'cat > /output.cpp && g++ -std=c++17 -Wall -Wextra -o /output /output.cpp && /output'"
+        "g++ -std=c++17 -Wall -Wextra -o main main.cpp && ./main"
     }
 
     fn docker_image(&self) -> &'static str {
@@ -34,15 +34,15 @@ impl Language for Cpp {
         "cpp"
     }
 
-    fn is_compiled(&self) -> bool {
-        true
-    }
-
     fn name(&self) -> &'static str {
         "cpp"
     }
 
+    fn project_command(&self) -> &'static str {
+        "g++ -std=c++17 -Wall -Wextra -o main *.cpp && ./main"
+    }
+
     fn security_config(&self) -> SecurityConfig {
-        SecurityConfig::default()
+        SecurityConfig::hardened()
     }
 }
@@ -24,6 +24,9 @@ mod cpp;
 mod python;
 mod scala;
 
+/// Directory inside the working directory that [`Language::support_files`] are written to.
+pub const SUPPORT_DIR: &str = ".compiler-bot";
+
 pub static LANGUAGES: LazyLock<HashMap<&'static str, Box<dyn Language + Send + Sync>>> =
     LazyLock::new(|| {
         let mut hashmap = HashMap::<&'static str, Box<dyn Language + Send + Sync>>::new();
@@ -34,17 +37,43 @@ pub static LANGUAGES: LazyLock<HashMap<&'static str, Box<dyn Language + Send + S
         hashmap
     });
 
+/// Finds the language whose source files use the given extension, ignoring case.
+pub fn language_for_extension(extension: &str) -> Option<&'static (dyn Language + Send + Sync)> {
+    LANGUAGES
+        .values()
+        .find(|language| language.file_extension().eq_ignore_ascii_case(extension))
+        .map(|language| language.as_ref())
+}
+
 pub trait Language {
+    /// Shell command that builds and runs the source file named by [`Language::source_file`]
+    /// from the current working directory.
     fn command(&self) -> &'static str;
 
     fn docker_image(&self) -> &'static str;
 
-    #[allow(dead_code)]
-    fn file_extension(&self) -> &'static str;
+    /// Environment variables set for the program.
+    fn environment(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
 
-    fn is_compiled(&self) -> bool;
+    fn file_extension(&self) -> &'static str;
 
     fn name(&self) -> &'static str;
 
+    /// Shell command that builds and runs a project of several files from the current working
+    /// directory, with the entry point's path in `$ENTRY`.
+    fn project_command(&self) -> &'static str;
+
     fn security_config(&self) -> SecurityConfig;
+
+    fn source_file(&self) -> String {
+        format!("main.{}", self.file_extension())
+    }
+
+    /// Supporting files written to [`SUPPORT_DIR`] in the working directory, as file names and
+    /// their contents.
+    fn support_files(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
 }
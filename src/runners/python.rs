@@ -23,26 +23,41 @@ pub struct Python;
 
 impl Language for Python {
     fn command(&self) -> &'static str {
-        "python3"
+        "python3 main.py"
     }
 
     fn docker_image(&self) -> &'static str {
         "compiler-bot-python-rt:latest"
     }
 
-    fn file_extension(&self) -> &'static str {
-        "py"
+    fn environment(&self) -> &'static [(&'static str, &'static str)] {
+        // Save matplotlib figures to the output directory since there is no display
+        &[
+            ("MPLBACKEND", "module://compiler_bot_backend"),
+            ("PYTHONPATH", "/sandbox/.compiler-bot"),
+        ]
     }
 
-    fn is_compiled(&self) -> bool {
-        false
+    fn file_extension(&self) -> &'static str {
+        "py"
     }
 
     fn name(&self) -> &'static str {
         "python"
     }
 
+    fn project_command(&self) -> &'static str {
+        "python3 \"$ENTRY\""
+    }
+
     fn security_config(&self) -> SecurityConfig {
-        SecurityConfig::default()
+        SecurityConfig::hardened()
+    }
+
+    fn support_files(&self) -> &'static [(&'static str, &'static str)] {
+        &[(
+            "compiler_bot_backend.py",
+            include_str!("compiler_bot_backend.py"),
+        )]
     }
 }
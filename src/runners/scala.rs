@@ -23,7 +23,7 @@ pub struct Scala;
 
 impl Language for Scala {
     fn command(&self) -> &'static str {
-        "bash -c 'cat > /Main.scala && scala /Main.scala'"
+        "scala main.scala"
     }
 
     fn docker_image(&self) -> &'static str {
@@ -34,19 +34,21 @@ impl Language for Scala {
         "scala"
     }
 
-    fn is_compiled(&self) -> bool {
-        true
-    }
-
     fn name(&self) -> &'static str {
         "scala"
     }
 
+    fn project_command(&self) -> &'static str {
+        "scalac -d main.jar *.scala && scala main.jar"
+    }
+
     fn security_config(&self) -> SecurityConfig {
         SecurityConfig {
             cpu_limit: "0.75".into(),
             memory_limit: "1024m".into(),
-            ..Default::default()
+            // Room for the compiler's output and the JVM's temporary files
+            tmpfs_size: "256m".into(),
+            ..SecurityConfig::hardened()
         }
     }
 }
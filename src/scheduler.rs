@@ -0,0 +1,282 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::watch;
+
+use crate::config::SchedulerConfig;
+
+/// Queue in front of the executor that caps how many jobs run at once, overall and per language.
+///
+/// Waiting jobs are served round-robin across users: a job is placed behind every job that was
+/// queued before it by a user with as many or fewer jobs outstanding, so someone submitting a
+/// burst of jobs can't hold everyone else up.
+pub struct Scheduler {
+    config: SchedulerConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    queue: VecDeque<QueuedJob>,
+    /// User and language of every running job by ID.
+    running: HashMap<u64, (u64, String)>,
+    running_per_language: HashMap<String, usize>,
+}
+
+struct QueuedJob {
+    id: u64,
+    user: u64,
+    language: String,
+    /// How many of the user's jobs were outstanding when this one was queued.
+    round: usize,
+    position: watch::Sender<usize>,
+}
+
+/// A job's place in the [`Scheduler`]. The job may run once its position reaches 0, and its slot
+/// is given up, or its place in the queue left, when the ticket is dropped.
+pub struct Ticket {
+    scheduler: Arc<Scheduler>,
+    id: u64,
+    position: watch::Receiver<usize>,
+}
+
+impl Ticket {
+    /// Number of jobs ahead of this one plus one, or 0 once it may run.
+    pub fn position(&mut self) -> usize {
+        *self.position.borrow_and_update()
+    }
+
+    /// Waits for the position to change, returning the new one.
+    pub async fn position_changed(&mut self) -> usize {
+        // The sender lives as long as the job is queued, and is only dropped once it may run
+        if self.position.changed().await.is_err() {
+            return 0;
+        }
+
+        self.position()
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.scheduler.release(self.id);
+    }
+}
+
+impl Scheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Queues a job for the user, starting it right away if there is room.
+    pub fn enqueue(self: &Arc<Self>, user: u64, language: &str) -> Ticket {
+        let mut state = self.state.lock().unwrap();
+
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let round = state.queue.iter().filter(|job| job.user == user).count()
+            + state
+                .running
+                .values()
+                .filter(|(running_user, _)| *running_user == user)
+                .count();
+        let index = state
+            .queue
+            .iter()
+            .position(|job| job.round > round)
+            .unwrap_or(state.queue.len());
+
+        let (position, receiver) = watch::channel(index + 1);
+        state.queue.insert(
+            index,
+            QueuedJob {
+                id,
+                user,
+                language: language.into(),
+                round,
+                position,
+            },
+        );
+        self.dispatch(&mut state);
+
+        Ticket {
+            scheduler: self.clone(),
+            id,
+            position: receiver,
+        }
+    }
+
+    /// Frees the job's slot, or removes it from the queue if it never started.
+    fn release(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(index) = state.queue.iter().position(|job| job.id == id) {
+            state.queue.remove(index);
+        } else if let Some((_, language)) = state.running.remove(&id)
+            && let Some(running) = state.running_per_language.get_mut(&language)
+        {
+            *running -= 1;
+        }
+
+        self.dispatch(&mut state);
+    }
+
+    /// Starts queued jobs in order for as long as there is room for them, skipping jobs whose
+    /// language is at its limit, and tells the rest where they now are in the queue.
+    fn dispatch(&self, state: &mut State) {
+        let mut index = 0;
+        while index < state.queue.len() && state.running.len() < self.config.max_jobs {
+            let job = &state.queue[index];
+            let running = state
+                .running_per_language
+                .get(&job.language)
+                .copied()
+                .unwrap_or(0);
+
+            if running >= self.config.language_limit(&job.language) {
+                index += 1;
+                continue;
+            }
+
+            let Some(job) = state.queue.remove(index) else {
+                break;
+            };
+            tracing::debug!("Starting job {} for {}", job.id, job.language);
+            *state
+                .running_per_language
+                .entry(job.language.clone())
+                .or_default() += 1;
+            state.running.insert(job.id, (job.user, job.language));
+            job.position.send_replace(0);
+        }
+
+        for (index, job) in state.queue.iter().enumerate() {
+            job.position.send_if_modified(|position| {
+                let changed = *position != index + 1;
+                *position = index + 1;
+                changed
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(max_jobs: usize, max_jobs_per_language: usize) -> Arc<Scheduler> {
+        Arc::new(Scheduler::new(SchedulerConfig {
+            max_jobs,
+            max_jobs_per_language,
+            language_limits: HashMap::new(),
+        }))
+    }
+
+    #[test]
+    fn jobs_start_while_there_is_room() {
+        let scheduler = scheduler(2, 2);
+        let mut first = scheduler.enqueue(1, "python");
+        let mut second = scheduler.enqueue(2, "python");
+        let mut third = scheduler.enqueue(3, "python");
+
+        assert_eq!(first.position(), 0);
+        assert_eq!(second.position(), 0);
+        assert_eq!(third.position(), 1);
+
+        drop(first);
+        assert_eq!(third.position(), 0);
+    }
+
+    #[test]
+    fn bursts_are_interleaved_with_other_users() {
+        let scheduler = scheduler(1, 1);
+        let _running = scheduler.enqueue(1, "python");
+
+        // One user queues a burst, then two others queue a job each
+        let mut burst = (0..3)
+            .map(|_| scheduler.enqueue(1, "python"))
+            .collect::<Vec<_>>();
+        let mut second_user = scheduler.enqueue(2, "python");
+        let mut third_user = scheduler.enqueue(3, "python");
+
+        // Each user gets a turn before anyone gets a second one
+        assert_eq!(second_user.position(), 1);
+        assert_eq!(third_user.position(), 2);
+        let positions = burst.iter_mut().map(Ticket::position).collect::<Vec<_>>();
+        assert_eq!(positions, [3, 4, 5]);
+    }
+
+    #[test]
+    fn full_languages_are_skipped() {
+        let scheduler = scheduler(3, 1);
+        let _python = scheduler.enqueue(1, "python");
+        let mut waiting_python = scheduler.enqueue(2, "python");
+        let mut cpp = scheduler.enqueue(3, "cpp");
+
+        assert_eq!(waiting_python.position(), 1);
+        assert_eq!(cpp.position(), 0);
+    }
+
+    #[test]
+    fn language_limits_override_the_default() {
+        let scheduler = Arc::new(Scheduler::new(SchedulerConfig {
+            max_jobs: 4,
+            max_jobs_per_language: 1,
+            language_limits: HashMap::from([("scala".into(), 2)]),
+        }));
+        let _first = scheduler.enqueue(1, "scala");
+        let mut second = scheduler.enqueue(2, "scala");
+        let mut third = scheduler.enqueue(3, "scala");
+
+        assert_eq!(second.position(), 0);
+        assert_eq!(third.position(), 1);
+    }
+
+    #[test]
+    fn leaving_the_queue_moves_everyone_up() {
+        let scheduler = scheduler(1, 1);
+        let _running = scheduler.enqueue(1, "python");
+        let waiting = scheduler.enqueue(2, "python");
+        let mut last = scheduler.enqueue(3, "python");
+        assert_eq!(last.position(), 2);
+
+        drop(waiting);
+        assert_eq!(last.position(), 1);
+    }
+
+    #[tokio::test]
+    async fn position_changes_are_announced() {
+        let scheduler = scheduler(1, 1);
+        let running = scheduler.enqueue(1, "python");
+        let mut waiting = scheduler.enqueue(2, "python");
+        assert_eq!(waiting.position(), 1);
+
+        drop(running);
+        assert_eq!(waiting.position_changed().await, 0);
+    }
+}
@@ -16,6 +16,11 @@
  * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
 use regex::Regex;
 use tracing::{Subscriber, level_filters::LevelFilter};
 use tracing_subscriber::{
@@ -24,8 +29,12 @@ use tracing_subscriber::{
     fmt::{Layer, time::OffsetTime},
     layer::SubscriberExt,
 };
+use zip::ZipArchive;
 
-pub fn subscriber() -> impl Subscriber {
+use crate::{config::InputConfig, executors::SourceFile};
+
+/// Logs events at `level` and above, along with where they came from.
+pub fn subscriber(level: LevelFilter) -> impl Subscriber {
     let fmt_layer = Layer::default()
         .pretty()
         .with_timer(OffsetTime::local_rfc_3339().unwrap())
@@ -33,35 +42,254 @@ pub fn subscriber() -> impl Subscriber {
         .with_level(true)
         .with_file(true)
         .with_line_number(true);
-    let targets_layer = Targets::new().with_default(LevelFilter::TRACE);
+    let targets_layer = Targets::new().with_default(level);
 
     Registry::default().with(fmt_layer).with(targets_layer)
 }
 
-pub fn extract_code_block(code: &str) -> Option<String> {
+pub fn extract_code_blocks(code: &str) -> Vec<String> {
     // Try to match code blocks with language specifiers
     let regex_with_lang = Regex::new(r"```(?:[a-zA-Z0-9]*)\n([\s\S]+?)```").unwrap();
-    if let Some(captures) = regex_with_lang.captures(code)
-        && let Some(matched_code) = captures.get(1)
-    {
-        // Remove the backticks and language specifier
-        let extracted_code = matched_code.as_str().trim();
-        if !extracted_code.is_empty() {
-            return Some(extracted_code.to_string());
-        }
+    let blocks = non_empty_captures(&regex_with_lang, code);
+    if !blocks.is_empty() {
+        return blocks;
     }
 
     // Try to match code blocks WITHOUT language specifiers
     let regex_plain = Regex::new(r"```([\s\S]+?)```").unwrap();
-    if let Some(captures) = regex_plain.captures(code)
-        && let Some(matched_code) = captures.get(1)
-    {
-        let extracted_code = matched_code.as_str().trim();
+    non_empty_captures(&regex_plain, code)
+}
+
+fn non_empty_captures(regex: &Regex, code: &str) -> Vec<String> {
+    regex
+        .captures_iter(code)
+        .filter_map(|captures| {
+            // Remove the backticks and language specifier
+            let extracted_code = captures.get(1)?.as_str().trim();
+            (!extracted_code.is_empty()).then(|| extracted_code.to_string())
+        })
+        .collect()
+}
+
+/// Splits a `// file: name` (or `# file: name`) label off the first line of a code block,
+/// returning the file name if present along with the remaining contents.
+pub fn parse_file_label(code_block: &str) -> (Option<String>, String) {
+    let regex_label = Regex::new(r"^(?://|#|--)\s*file:\s*(\S+)\s*$").unwrap();
+    let (first_line, rest) = code_block.split_once('\n').unwrap_or((code_block, ""));
+
+    match regex_label.captures(first_line.trim()) {
+        Some(captures) => (Some(captures[1].to_string()), rest.to_string()),
+        None => (None, code_block.to_string()),
+    }
+}
+
+/// Reads the files of a zip archive, skipping directories and refusing archives that would
+/// exceed the configured file count or total size once extracted.
+pub fn extract_zip(bytes: &[u8], input: &InputConfig) -> Result<Vec<SourceFile>, String> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("Invalid zip archive: {e}"))?;
+
+    let mut files = Vec::new();
+    let mut total_size = 0;
+
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| format!("Invalid zip archive: {e}"))?;
+        if entry.is_dir() {
+            continue;
+        }
 
-        if !extracted_code.is_empty() {
-            return Some(extracted_code.to_string());
+        let name = entry
+            .enclosed_name()
+            .and_then(|path| path.to_str().map(String::from))
+            .ok_or("Zip archive contains an unsafe file name")?;
+
+        // Skip the resource forks macOS adds to archives it creates
+        if name.starts_with("__MACOSX/") {
+            continue;
+        }
+
+        if files.len() >= input.max_files {
+            return Err(format!(
+                "Zip archive contains more than {} files",
+                input.max_files
+            ));
+        }
+
+        // Don't trust the declared size, read at most what is left of the budget
+        let remaining = input.max_source_size - total_size;
+        let mut contents = String::new();
+        entry
+            .by_ref()
+            .take(remaining as u64 + 1)
+            .read_to_string(&mut contents)
+            .map_err(|_| format!("`{name}` is not a valid UTF-8 text file"))?;
+
+        total_size += contents.len();
+        if total_size > input.max_source_size {
+            return Err(format!(
+                "Zip archive is larger than the {} byte limit once extracted",
+                input.max_source_size
+            ));
         }
+
+        files.push(SourceFile { name, contents });
     }
 
-    None
+    Ok(files)
+}
+
+pub fn has_extension(filename: &str, extension: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+pub fn is_image(filename: &str) -> bool {
+    ["png", "jpg", "jpeg", "gif", "webp"]
+        .iter()
+        .any(|extension| has_extension(filename, extension))
+}
+
+/// Compares secrets such as tokens without returning early, so their contents can't be guessed
+/// from how long a comparison takes.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Percent-encodes every byte except unreserved characters, for use in a URL path segment or
+/// query string value.
+pub fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    fn zip(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn parse_file_label_splits_off_the_label() {
+        assert_eq!(
+            parse_file_label("// file: src/main.cpp\nint main() {}"),
+            (Some("src/main.cpp".into()), "int main() {}".into())
+        );
+        assert_eq!(
+            parse_file_label("#file:util.py\nx = 1"),
+            (Some("util.py".into()), "x = 1".into())
+        );
+        assert_eq!(
+            parse_file_label("-- file: query.sql"),
+            (Some("query.sql".into()), String::new())
+        );
+    }
+
+    #[test]
+    fn parse_file_label_leaves_unlabeled_blocks_alone() {
+        let code = "// not a file: label\nprint(1)";
+        assert_eq!(parse_file_label(code), (None, code.into()));
+        assert_eq!(parse_file_label("print(1)"), (None, "print(1)".into()));
+    }
+
+    #[test]
+    fn extract_zip_reads_files_and_skips_macos_forks() {
+        let archive = zip(&[
+            ("main.py", "import util"),
+            ("util/__init__.py", "x = 1"),
+            ("__MACOSX/._main.py", "junk"),
+        ]);
+
+        let files = extract_zip(&archive, &InputConfig::default()).unwrap();
+        let names = files
+            .iter()
+            .map(|file| file.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["main.py", "util/__init__.py"]);
+        assert_eq!(files[0].contents, "import util");
+    }
+
+    #[test]
+    fn extract_zip_keeps_paths_inside_the_archive() {
+        for name in ["../escape.py", "src/../../escape.py"] {
+            let archive = zip(&[(name, "x = 1")]);
+            assert_eq!(
+                extract_zip(&archive, &InputConfig::default()).unwrap_err(),
+                "Zip archive contains an unsafe file name"
+            );
+        }
+
+        // Absolute paths are taken as relative to the archive's root
+        let archive = zip(&[("/etc/passwd", "x = 1")]);
+        let files = extract_zip(&archive, &InputConfig::default()).unwrap();
+        assert_eq!(files[0].name, "etc/passwd");
+    }
+
+    #[test]
+    fn extract_zip_enforces_the_limits() {
+        let input = InputConfig {
+            max_files: 2,
+            max_source_size: 10,
+            ..Default::default()
+        };
+
+        let too_many = zip(&[("a.py", ""), ("b.py", ""), ("c.py", "")]);
+        assert!(extract_zip(&too_many, &input).is_err());
+
+        let too_large = zip(&[("a.py", "123456"), ("b.py", "123456")]);
+        assert!(extract_zip(&too_large, &input).is_err());
+
+        let just_fits = zip(&[("a.py", "12345"), ("b.py", "12345")]);
+        assert_eq!(extract_zip(&just_fits, &input).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn extract_zip_rejects_invalid_archives_and_binary_files() {
+        assert!(extract_zip(b"not a zip", &InputConfig::default()).is_err());
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("main.py", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&[0xff, 0xfe, 0x00]).unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        assert!(extract_zip(&archive, &InputConfig::default()).is_err());
+    }
+
+    #[test]
+    fn percent_encode_keeps_only_unreserved_characters() {
+        assert_eq!(
+            percent_encode("sandbox_python-3.12~"),
+            "sandbox_python-3.12~"
+        );
+        assert_eq!(percent_encode("!room:example.org"), "%21room%3Aexample.org");
+        assert_eq!(
+            percent_encode(r#"{"label":["a=b"]}"#),
+            "%7B%22label%22%3A%5B%22a%3Db%22%5D%7D"
+        );
+    }
 }
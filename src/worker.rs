@@ -0,0 +1,129 @@
+/*
+ * Compiler-Bot: compiler bot for Unofficial.CSE
+ * Copyright (C) 2025  Unofficial.CSE contributors
+ *
+ * Compiler-Bot is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Compiler-Bot is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with Compiler-Bot.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{env, net::SocketAddr, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde_json::json;
+
+use crate::{
+    config::{Backend, BotConfig},
+    executors::{self, ExecutionRequest, Executor},
+    utils::constant_time_eq,
+};
+
+/// Environment variable holding the token the bot authenticates to workers with.
+pub const TOKEN_VAR: &str = "WORKER_TOKEN";
+
+struct Worker {
+    executor: Arc<dyn Executor>,
+    token: String,
+}
+
+/// Serves the job API for bots on other machines, running their jobs with the configured
+/// backend. Every request has to carry the shared token as a bearer token.
+///
+/// - `GET /health` lists the languages the worker runs.
+/// - `POST /jobs` runs an [`ExecutionRequest`] and answers with its `ExecutionResult`.
+pub async fn serve(config: BotConfig) -> Result<(), String> {
+    let token = env::var(TOKEN_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| format!("{TOKEN_VAR} environment variable is not set"))?;
+    if matches!(config.backend, Backend::Remote) {
+        return Err("Workers can't use the remote backend themselves".into());
+    }
+
+    let address = config.worker.listen.parse::<SocketAddr>().map_err(|e| {
+        format!(
+            "Invalid worker listen address {}: {e}",
+            config.worker.listen
+        )
+    })?;
+
+    let app = app(&config, executors::from_config(&config), token);
+
+    tracing::info!("Worker listening on {address}");
+    axum::Server::try_bind(&address)
+        .map_err(|e| format!("Failed to listen on {address}: {e}"))?
+        .serve(app.into_make_service())
+        .await
+        .map_err(|e| format!("Worker server failed: {e}"))
+}
+
+/// Routes of the job API, running jobs with `executor`.
+pub fn app(config: &BotConfig, executor: Arc<dyn Executor>, token: String) -> Router {
+    // JSON escaping can make a request several times larger than the code and input in it
+    let body_limit = 8 * (config.input.max_source_size + config.input.max_stdin_size) + 64 * 1024;
+    let worker = Arc::new(Worker { executor, token });
+
+    Router::new()
+        .route("/health", get(health))
+        .route("/jobs", post(run_job))
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(middleware::from_fn_with_state(worker.clone(), authenticate))
+        .with_state(worker)
+}
+
+/// Rejects requests without the worker's token.
+async fn authenticate<B>(
+    State(worker): State<Arc<Worker>>,
+    headers: HeaderMap,
+    request: axum::http::Request<B>,
+    next: Next<B>,
+) -> Response {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), worker.token.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+async fn health(State(worker): State<Arc<Worker>>) -> Json<serde_json::Value> {
+    Json(json!({ "languages": worker.executor.supported_languages() }))
+}
+
+async fn run_job(
+    State(worker): State<Arc<Worker>>,
+    Json(request): Json<ExecutionRequest>,
+) -> Response {
+    tracing::info!("Running remote job for language: {}", request.language);
+
+    match worker.executor.execute(&request).await {
+        Ok(result) => Json(result).into_response(),
+        // Refused requests and sandbox failures alike are reported to the user by the bot
+        Err(error) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": error })),
+        )
+            .into_response(),
+    }
+}